@@ -1,12 +1,22 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     error::Error,
+    fmt, mem,
+    path::Path,
 };
 
 use monostate::MustBe;
 use serde::Deserialize;
 
+use crate::{
+    Language,
+    codegen::diagnostic::{Code, Diagnostic, Diagnostics, Pointer, diag},
+    util::glob,
+};
+
+mod bundle;
 pub mod csharp;
+pub mod diagnostic;
 pub mod go;
 pub mod java;
 pub mod python;
@@ -16,31 +26,223 @@ mod shared;
 
 const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
 
-#[derive(Debug, Default)]
+/// The extension that explicitly marks a schema as managed (or unmanaged).
+const MANAGED_EXTENSION: &str = "x-turbopuffer-managed";
+
+/// Selects which schemas in the OpenAPI spec are managed, i.e. have code
+/// generated for them.
+///
+/// A schema is managed if its name starts with one of `prefixes` or appears
+/// in `names`. The `x-turbopuffer-managed` extension on a schema overrides
+/// that decision in either direction. Schemas whose names match any of the
+/// `exclude` globs are never managed.
+///
+/// If any `roots` are given, the selection is further narrowed to the roots
+/// themselves plus the schemas selected by the above rules that are reachable
+/// from the roots.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaSelector {
+    pub prefixes: Vec<String>,
+    pub names: BTreeSet<String>,
+    pub exclude: Vec<String>,
+    pub roots: BTreeSet<String>,
+}
+
+impl SchemaSelector {
+    fn is_managed(&self, name: &str, marked: Option<bool>) -> bool {
+        if self.exclude.iter().any(|glob| glob::matches(glob, name)) {
+            return false;
+        }
+        marked.unwrap_or_else(|| {
+            self.names.contains(name) || self.prefixes.iter().any(|p| name.starts_with(p))
+        })
+    }
+}
+
+/// Options that tailor the generated code for a single target.
+///
+/// Every option is optional. Each backend falls back to the coordinates used
+/// by the official turbopuffer SDK for its language.
+#[derive(Debug, Default, Clone, Deserialize, clap::Args)]
+#[serde(deny_unknown_fields)]
+pub struct TargetOptions {
+    /// The package to generate code into. Applies to Go (the package name)
+    /// and Java (the Kotlin package).
+    #[arg(long)]
+    pub package: Option<String>,
+    /// The namespace to generate code into. Applies to C#.
+    #[arg(long)]
+    pub namespace: Option<String>,
+    /// Where to import referenced, non-generated types from. Applies to
+    /// TypeScript (a module specifier) and Python (a package, which may be
+    /// relative, like `.` or `..types`).
+    #[arg(long)]
+    pub import_path: Option<String>,
+    /// The import path of the JSON encoding package. Applies to Go.
+    #[arg(long)]
+    pub json_import: Option<String>,
+    /// The package containing the SDK's core Jackson helpers. Applies to
+    /// Java.
+    #[arg(long)]
+    pub core_package: Option<String>,
+    /// Represent 64-bit integers as `bigint` rather than `number`, which
+    /// can't represent integers above 2^53 exactly. Applies to TypeScript.
+    #[arg(long)]
+    #[serde(default)]
+    pub bigint: bool,
+}
+
+impl TargetOptions {
+    /// Returns an error if any option is set that doesn't apply to
+    /// `language`.
+    pub fn validate(&self, language: Language) -> Result<(), Box<dyn Error>> {
+        let options = [
+            (
+                "package",
+                self.package.is_some(),
+                &[Language::Go, Language::Java][..],
+            ),
+            ("namespace", self.namespace.is_some(), &[Language::CSharp]),
+            (
+                "import_path",
+                self.import_path.is_some(),
+                &[Language::Typescript, Language::Python],
+            ),
+            ("json_import", self.json_import.is_some(), &[Language::Go]),
+            (
+                "core_package",
+                self.core_package.is_some(),
+                &[Language::Java],
+            ),
+            ("bigint", self.bigint, &[Language::Typescript]),
+        ];
+        for (name, set, languages) in options {
+            if set && !languages.contains(&language) {
+                Err(format!(
+                    "option {name} does not apply to {language} targets"
+                ))?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct OpenApiSpec {
     pub managed_schemas: BTreeMap<String, OpenApiSchema>,
     pub unmanaged_schemas: BTreeSet<String>,
+    /// The location in the spec of each managed schema that was derived from
+    /// part of another schema (e.g., by [`shared::extract_any_of_tuples`]),
+    /// rather than defined directly in `#/components/schemas`.
+    pub origins: BTreeMap<String, Pointer>,
+    /// The location that each schema imported into `#/components/schemas`
+    /// from elsewhere came from (see [`bundle::bundle`]), which diagnostics
+    /// are relocated to with [`Diagnostics::relocate`].
+    pub imports: BTreeMap<String, Pointer>,
 }
 
 impl OpenApiSpec {
-    pub fn parse(yaml: &str, type_prefixes: &[&str]) -> Result<Self, Box<dyn Error>> {
-        let mut openapi: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+    /// Parses an OpenAPI specification in either YAML or JSON format. `path`
+    /// is the location of the specification, if it's a local file, against
+    /// which references to other files are resolved.
+    pub fn parse(
+        content: &str,
+        path: Option<&Path>,
+        selector: &SchemaSelector,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut openapi = parse_document(content)?;
+        let imports = bundle::bundle(&mut openapi, path)?;
+        let mut spec = Self::parse_bundled(openapi, selector)
+            .map_err(|e| Diagnostics::from_error(e).relocate(&imports))?;
+        spec.imports = imports;
+        Ok(spec)
+    }
+
+    /// Parses an OpenAPI specification whose references have all been
+    /// bundled into `#/components/schemas`.
+    fn parse_bundled(
+        mut openapi: serde_yaml::Value,
+        selector: &SchemaSelector,
+    ) -> Result<Self, Box<dyn Error>> {
         let schemas = openapi["components"]["schemas"]
             .as_mapping_mut()
-            .ok_or_else(|| "no schemas found in OpenAPI spec")?;
+            .ok_or_else(|| {
+                Diagnostic::new(Code::InvalidSpec, "no schemas found in OpenAPI spec")
+            })?;
+
+        for root in &selector.roots {
+            if !schemas.contains_key(root.as_str()) {
+                Err(Diagnostic::new(
+                    Code::SchemaNotFound,
+                    format!("root schema not found: {root}"),
+                ))?
+            }
+        }
 
         let mut spec = OpenApiSpec::default();
+        let mut candidates = BTreeMap::new();
         let mut all_schemas = BTreeSet::new();
-        for (k, v) in schemas {
+        for (k, v) in schemas.iter_mut() {
             let k = k.as_str().unwrap().to_string();
-            if type_prefixes.iter().any(|prefix| k.starts_with(prefix)) {
-                let schema = serde_yaml::from_value(v.clone())?;
-                spec.managed_schemas.insert(k, schema);
+            let marked = match v.as_mapping_mut().and_then(|m| m.remove(MANAGED_EXTENSION)) {
+                None => None,
+                Some(serde_yaml::Value::Bool(marked)) => Some(marked),
+                Some(_) => Err(diag(
+                    Code::InvalidSchema,
+                    &Pointer::schema(&k),
+                    format!("{MANAGED_EXTENSION} must be a boolean"),
+                ))?,
+            };
+            if selector.roots.contains(&k) || selector.is_managed(&k, marked) {
+                candidates.insert(k, v.clone());
             } else {
                 all_schemas.insert(k);
             }
         }
 
+        // Report every schema that fails to parse, not just the first.
+        let mut diagnostics = Diagnostics::default();
+        if selector.roots.is_empty() {
+            for (k, v) in candidates {
+                match parse_schema(&k, v, schemas) {
+                    Ok(schema) => {
+                        spec.managed_schemas.insert(k, schema);
+                    }
+                    Err(d) => diagnostics.push(d),
+                }
+            }
+        } else {
+            // Only manage the candidates reachable from the roots through
+            // other candidates. Unreachable candidates are never even
+            // deserialized, so a stale or experimental schema can't break the
+            // run.
+            let mut queue: Vec<String> = selector.roots.iter().cloned().collect();
+            while let Some(name) = queue.pop() {
+                let Some(v) = candidates.remove(&name) else {
+                    continue;
+                };
+                let schema = match parse_schema(&name, v, schemas) {
+                    Ok(schema) => schema,
+                    Err(d) => {
+                        diagnostics.push(d);
+                        continue;
+                    }
+                };
+                let mut refs = BTreeSet::new();
+                schema.collect_refs(&mut refs);
+                queue.extend(refs);
+                spec.managed_schemas.insert(name, schema);
+            }
+            for name in candidates.into_keys() {
+                crate::log!("skipping schema unreachable from roots: {name}");
+                all_schemas.insert(name);
+            }
+        }
+        if !diagnostics.is_empty() {
+            Err(diagnostics)?
+        }
+        resolve_discriminators(&mut spec.managed_schemas)?;
+
         // Filter unmanaged schemas to only those referenced by managed schemas.
         let mut referenced = BTreeSet::new();
         for schema in spec.managed_schemas.values() {
@@ -50,21 +252,1000 @@ impl OpenApiSpec {
 
         Ok(spec)
     }
+
+    /// Returns the location in the spec of the named managed schema.
+    pub fn pointer(&self, name: &str) -> Pointer {
+        schema_pointer(&self.origins, name)
+    }
+}
+
+/// Returns the location in the spec of the named schema, consulting `origins`
+/// for schemas that were derived from part of another schema.
+fn schema_pointer(origins: &BTreeMap<String, Pointer>, name: &str) -> Pointer {
+    origins
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| Pointer::schema(name))
+}
+
+/// Checks the `discriminator` of each `oneOf` schema against its variants, and
+/// pins each variant's discriminator property to the `const` value that
+/// identifies the variant, so that backends can read the tags off the
+/// variants themselves.
+fn resolve_discriminators(
+    schemas: &mut BTreeMap<String, OpenApiSchema>,
+) -> Result<(), Diagnostics> {
+    let unions: Vec<_> = schemas
+        .iter()
+        .filter_map(|(name, schema)| match schema {
+            OpenApiSchema::OneOf {
+                one_of,
+                discriminator,
+                ..
+            } => Some((name.clone(), one_of.clone(), discriminator.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let mut diagnostics = Diagnostics::default();
+    for (name, one_of, discriminator) in unions {
+        let pointer = Pointer::schema(&name);
+        if let Err(d) = resolve_discriminator(schemas, &pointer, &one_of, &discriminator) {
+            diagnostics.push(d);
+        }
+    }
+    match diagnostics.is_empty() {
+        true => Ok(()),
+        false => Err(diagnostics),
+    }
+}
+
+fn resolve_discriminator(
+    schemas: &mut BTreeMap<String, OpenApiSchema>,
+    pointer: &Pointer,
+    one_of: &[OpenApiSchema],
+    discriminator: &Discriminator,
+) -> Result<(), Diagnostic> {
+    let invalid =
+        |pointer: &Pointer, message: String| diag(Code::InvalidDiscriminator, pointer, message);
+    let property = &discriminator.property_name;
+
+    // Invert the mapping, so that it maps variant names to tags.
+    let mut mapped = BTreeMap::new();
+    for (tag, target) in &discriminator.mapping {
+        let target = target.strip_prefix(SCHEMA_REF_PREFIX).unwrap_or(target);
+        if mapped.insert(target, tag.as_str()).is_some() {
+            Err(invalid(
+                &pointer.join("discriminator"),
+                format!("multiple discriminator values map to {target}"),
+            ))?
+        }
+    }
+
+    let mut tags = BTreeSet::new();
+    for (i, arm) in one_of.iter().enumerate() {
+        let arm_pointer = pointer.join("oneOf").join(i);
+        let OpenApiSchema::Ref { sref, .. } = arm else {
+            Err(invalid(
+                &arm_pointer,
+                "variants of a oneOf with a discriminator must be references".into(),
+            ))?
+        };
+        let variant = strip_schema_ref_prefix(sref, &arm_pointer)?;
+        let Some(OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        }) = schemas.get_mut(variant)
+        else {
+            Err(invalid(
+                &arm_pointer,
+                format!("variant {variant} must be a managed object schema"),
+            ))?
+        };
+        let property_pointer = Pointer::schema(variant).join("properties").join(property);
+        let (Some(schema), true) = (properties.get_mut(property), required.contains(property))
+        else {
+            Err(invalid(
+                &Pointer::schema(variant),
+                format!("variant {variant} must have the required property {property}"),
+            ))?
+        };
+        let sconst = match &*schema {
+            OpenApiSchema::Const {
+                value: ConstValue::String(sconst),
+                ..
+            } => Some(sconst.as_str()),
+            OpenApiSchema::String { .. } => None,
+            _ => Err(invalid(
+                &property_pointer,
+                "discriminator property must be a string".into(),
+            ))?,
+        };
+        let tag = match (mapped.remove(variant), sconst) {
+            (Some(tag), Some(sconst)) if sconst != tag => Err(invalid(
+                &property_pointer,
+                format!("const {sconst} contradicts the discriminator mapping's {tag}"),
+            ))?,
+            (Some(tag), _) => tag.to_string(),
+            (None, Some(sconst)) => sconst.to_string(),
+            (None, None) => variant.to_string(),
+        };
+        if let OpenApiSchema::String {
+            description,
+            title,
+            deprecated,
+            x_turbopuffer_deprecated_message,
+            ..
+        } = schema
+        {
+            *schema = OpenApiSchema::Const {
+                description: description.take(),
+                value: ConstValue::String(tag.clone()),
+                title: title.take(),
+                deprecated: *deprecated,
+                x_turbopuffer_deprecated_message: x_turbopuffer_deprecated_message.take(),
+            }
+        }
+        if !tags.insert(tag.clone()) {
+            Err(invalid(
+                &arm_pointer,
+                format!("duplicate discriminator value: {tag}"),
+            ))?
+        }
+    }
+    if let Some(variant) = mapped.keys().next() {
+        Err(invalid(
+            &pointer.join("discriminator"),
+            format!("discriminator mapping names {variant}, which is not a variant"),
+        ))?
+    }
+    Ok(())
+}
+
+/// Parses an OpenAPI document in either YAML or JSON format.
+fn parse_document(content: &str) -> Result<serde_yaml::Value, Diagnostic> {
+    // YAML is nominally a superset of JSON, but in practice YAML parsers
+    // reject some valid JSON (e.g., the `\/` escape), so parse anything
+    // that looks like JSON as JSON.
+    if is_json(content) {
+        serde_json::from_str(content)
+            .map_err(|e| Diagnostic::new(Code::InvalidSpec, format!("invalid JSON: {e}")))
+    } else {
+        serde_yaml::from_str(content)
+            .map_err(|e| Diagnostic::new(Code::InvalidSpec, format!("invalid YAML: {e}")))
+    }
+}
+
+fn is_json(content: &str) -> bool {
+    content.trim_start().starts_with('{')
+}
+
+/// Returns a copy of the OpenAPI document `content` that Stainless can read,
+/// in the same format. Stainless chokes on the JSON Schema 2020-12 `items`
+/// of tuples, so they're spelled `additionalItems` instead, which it ignores.
+pub fn stainless_spec(content: &str) -> Result<String, Box<dyn Error>> {
+    fn rewrite(value: &mut serde_yaml::Value) {
+        match value {
+            serde_yaml::Value::Mapping(map) => {
+                if is_tuple_with_items(map) && !map.contains_key("additionalItems") {
+                    rename_tuple_items(map);
+                }
+                for (_, child) in map.iter_mut() {
+                    rewrite(child);
+                }
+            }
+            serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(rewrite),
+            _ => {}
+        }
+    }
+
+    let mut openapi = parse_document(content)?;
+    rewrite(&mut openapi);
+    match is_json(content) {
+        true => Ok(serde_json::to_string_pretty(&openapi)? + "\n"),
+        false => Ok(serde_yaml::to_string(&openapi)?),
+    }
+}
+
+/// Parses the named schema. `schemas` are all the spec's schemas, for
+/// resolving references during normalization.
+fn parse_schema(
+    name: &str,
+    mut value: serde_yaml::Value,
+    schemas: &serde_yaml::Mapping,
+) -> Result<OpenApiSchema, Diagnostic> {
+    let pointer = Pointer::schema(name);
+    let mut components = Components {
+        schemas,
+        resolving: vec![name.to_string()],
+    };
+    normalize(&mut value, &pointer, &mut components)?;
+    serde_yaml::from_value(value.clone()).map_err(|e| {
+        // Serde can't say which variant of an untagged enum came closest.
+        explain_mismatch(&value, &pointer)
+            .unwrap_or_else(|| diag(Code::InvalidSchema, &pointer, e.to_string()))
+    })
+}
+
+/// Explains why the schema `value` at `pointer` doesn't deserialize, by
+/// finding the innermost subschema that doesn't, and comparing its keys with
+/// those of each of [`SCHEMA_FORMS`]. Returns `None` if `value` isn't a
+/// mapping.
+fn explain_mismatch(value: &serde_yaml::Value, pointer: &Pointer) -> Option<Diagnostic> {
+    let map = value.as_mapping()?;
+    let parses =
+        |value: &serde_yaml::Value| serde_yaml::from_value::<OpenApiSchema>(value.clone()).is_ok();
+
+    // A schema can't deserialize if one of its subschemas can't, so blame the
+    // subschema.
+    for (key, child) in map {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let children: Vec<_> = match key {
+            "allOf" | "anyOf" | "oneOf" | "prefixItems" => (child.as_sequence().into_iter())
+                .flatten()
+                .enumerate()
+                .map(|(i, child)| (child, pointer.join(key).join(i)))
+                .collect(),
+            "properties" => (child.as_mapping().into_iter())
+                .flatten()
+                .map(|(name, child)| {
+                    let name = name.as_str().unwrap_or_default();
+                    (child, pointer.join(key).join(name))
+                })
+                .collect(),
+            "items" | "additionalItems" | "additionalProperties" | "propertyNames" => {
+                vec![(child, pointer.join(key))]
+            }
+            _ => vec![],
+        };
+        for (child, pointer) in children {
+            if child.is_mapping() && !parses(child) {
+                return explain_mismatch(child, &pointer);
+            }
+        }
+    }
+
+    let invalid = |pointer: &Pointer, message: String| diag(Code::InvalidSchema, pointer, message);
+    let ty = map.get("type");
+    let unsupported_ty = ty.filter(|ty| !SCHEMA_FORMS.iter().any(|form| ty.as_str() == form.ty));
+    if let Some(ty) = unsupported_ty {
+        let ty = serde_json::to_string(ty).unwrap_or_default();
+        return Some(invalid(
+            &pointer.join("type"),
+            format!("unsupported type: {ty}"),
+        ));
+    }
+
+    // The closest form is the one with the fewest unsupported and missing
+    // keys, among those with the schema's type.
+    let keys: Vec<_> = map.keys().filter_map(serde_yaml::Value::as_str).collect();
+    let (form, unsupported, missing) = SCHEMA_FORMS
+        .iter()
+        .filter(|form| form.ty.is_none() || form.ty == ty.and_then(serde_yaml::Value::as_str))
+        .map(|form| {
+            let unsupported: Vec<_> = (keys.iter().copied())
+                .filter(|key| !form.supports(key))
+                .collect();
+            let missing: Vec<_> = (form.required.iter().copied())
+                .filter(|key| !keys.contains(key))
+                .collect();
+            (form, unsupported, missing)
+        })
+        .min_by_key(|(_, unsupported, missing)| unsupported.len() + missing.len())?;
+    let list = |keys: &[&str]| {
+        let keys: Vec<_> = keys.iter().map(|key| format!("`{key}`")).collect();
+        keys.join(", ")
+    };
+    let mut problems = vec![];
+    if !unsupported.is_empty() {
+        problems.push(format!("doesn't support {}", list(&unsupported)));
+    }
+    if !missing.is_empty() {
+        problems.push(format!("requires {}", list(&missing)));
+    }
+    if problems.is_empty() {
+        // The keys are right, so a value must be wrong. If dropping an
+        // optional key makes the schema deserialize, it's that key's.
+        let key = keys.iter().find(|key| {
+            let mut map = map.clone();
+            map.remove(**key);
+            parses(&serde_yaml::Value::Mapping(map))
+        });
+        return Some(match key {
+            Some(key) => invalid(
+                &pointer.join(key),
+                format!("invalid value for `{key}` of {}", form.name),
+            ),
+            None => invalid(pointer, format!("invalid value in {}", form.name)),
+        });
+    }
+    Some(invalid(
+        pointer,
+        format!(
+            "schema matches no supported form; the closest is {}, which {}",
+            form.name,
+            problems.join(" and ")
+        ),
+    ))
+}
+
+/// Rewrites the alternative spellings of schemas that [`OpenApiSchema`] has
+/// no direct representation for in `value` and its subschemas.
+fn normalize(
+    value: &mut serde_yaml::Value,
+    pointer: &Pointer,
+    components: &mut Components,
+) -> Result<(), Diagnostic> {
+    let Some(map) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+
+    for (key, child) in map.iter_mut() {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        match key {
+            "allOf" | "anyOf" | "oneOf" | "prefixItems" => {
+                for (i, child) in child.as_sequence_mut().into_iter().flatten().enumerate() {
+                    normalize(child, &pointer.join(key).join(i), components)?;
+                }
+            }
+            "properties" => {
+                for (name, child) in child.as_mapping_mut().into_iter().flatten() {
+                    let name = name.as_str().unwrap_or_default();
+                    normalize(child, &pointer.join(key).join(name), components)?;
+                }
+            }
+            "items" | "additionalItems" | "additionalProperties" | "propertyNames" => {
+                normalize(child, &pointer.join(key), components)?
+            }
+            _ => {}
+        }
+    }
+
+    check_default(value, pointer)?;
+    normalize_tuple_items(value, pointer)?;
+    normalize_exclusive_bounds(value);
+    check_integer_bounds(value, pointer)?;
+    normalize_all_of(value, pointer, components)?;
+    normalize_one_of(value);
+    normalize_enum(value, pointer)?;
+    normalize_nullable(value);
+    Ok(())
+}
+
+/// The spec's schemas, for resolving the references in `allOf`s.
+struct Components<'a> {
+    schemas: &'a serde_yaml::Mapping,
+    /// The schemas being normalized, innermost last, to detect cycles.
+    resolving: Vec<String>,
+}
+
+impl Components<'_> {
+    /// If `schema` is a reference, returns the normalized schema it refers to
+    /// and its location. Otherwise returns `schema` as is.
+    fn resolve(
+        &mut self,
+        schema: serde_yaml::Value,
+        pointer: Pointer,
+    ) -> Result<(serde_yaml::Value, Pointer), Diagnostic> {
+        let Some(sref) = schema.get("$ref").and_then(serde_yaml::Value::as_str) else {
+            return Ok((schema, pointer));
+        };
+        let name = strip_schema_ref_prefix(sref, &pointer)?;
+        let Some(target) = self.schemas.get(name) else {
+            Err(diag(
+                Code::SchemaNotFound,
+                &pointer,
+                format!("schema not found: {name}"),
+            ))?
+        };
+        if self.resolving.iter().any(|n| n == name) {
+            Err(diag(
+                Code::InvalidSchema,
+                &pointer,
+                format!("allOf refers back to {name}"),
+            ))?
+        }
+        let mut target = target.clone();
+        let target_pointer = Pointer::schema(name);
+        self.resolving.push(name.to_string());
+        let result = normalize(&mut target, &target_pointer, self)
+            .and_then(|()| self.resolve(target, target_pointer));
+        self.resolving.pop();
+        result
+    }
+}
+
+/// Rewrites an `allOf` of object schemas and references to object schemas as
+/// a single object schema with the properties of all of them. Any keywords
+/// beside the `allOf` count as one more object schema, except for
+/// [`DOC_KEYWORDS`] and `nullable`, which apply to the result.
+fn normalize_all_of(
+    value: &mut serde_yaml::Value,
+    pointer: &Pointer,
+    components: &mut Components,
+) -> Result<(), Diagnostic> {
+    let Some(map) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+    let Some(parts) = map.remove("allOf") else {
+        return Ok(());
+    };
+    let serde_yaml::Value::Sequence(parts) = parts else {
+        Err(diag(Code::InvalidSchema, pointer, "allOf must be a list"))?
+    };
+
+    let mut merged = serde_yaml::Mapping::new();
+    for key in DOC_KEYWORDS.iter().chain(&["nullable"]) {
+        if let Some(v) = map.remove(*key) {
+            merged.insert((*key).into(), v);
+        }
+    }
+    let mut properties = serde_yaml::Mapping::new();
+    let mut required = vec![];
+    for (i, part) in parts.into_iter().enumerate() {
+        let (part, part_pointer) = components.resolve(part, pointer.join("allOf").join(i))?;
+        merge_object(&mut properties, &mut required, part, &part_pointer)?;
+    }
+    if !map.is_empty() {
+        let rest = serde_yaml::Value::Mapping(mem::take(map));
+        merge_object(&mut properties, &mut required, rest, pointer)?;
+    }
+
+    merged.insert("type".into(), "object".into());
+    merged.insert("properties".into(), properties.into());
+    if !required.is_empty() {
+        merged.insert("required".into(), required.into());
+    }
+    *map = merged;
+    Ok(())
 }
 
+/// Adds the properties and required properties of the object schema `part`
+/// at `pointer` to `properties` and `required`.
+fn merge_object(
+    properties: &mut serde_yaml::Mapping,
+    required: &mut Vec<serde_yaml::Value>,
+    part: serde_yaml::Value,
+    pointer: &Pointer,
+) -> Result<(), Diagnostic> {
+    let invalid = |pointer: &Pointer, message: String| diag(Code::InvalidSchema, pointer, message);
+    let serde_yaml::Value::Mapping(part) = part else {
+        Err(invalid(pointer, "allOf parts must be objects".into()))?
+    };
+
+    // Two schemas for the same property conflict unless they differ only in
+    // their documentation.
+    let strip_docs = |schema: &serde_yaml::Value| {
+        let mut schema = schema.clone();
+        if let Some(map) = schema.as_mapping_mut() {
+            for key in DOC_KEYWORDS {
+                map.remove(key);
+            }
+        }
+        schema
+    };
+
+    for (key, v) in part {
+        match key.as_str().unwrap_or_default() {
+            "type" if v == "object" => {}
+            key if DOC_KEYWORDS.contains(&key) => {}
+            "properties" => {
+                let serde_yaml::Value::Mapping(v) = v else {
+                    Err(invalid(pointer, "properties must be a map".into()))?
+                };
+                for (name, schema) in v {
+                    match properties.get(&name) {
+                        Some(existing) if strip_docs(existing) != strip_docs(&schema) => {
+                            let name = name.as_str().unwrap_or_default();
+                            Err(invalid(
+                                &pointer.join("properties").join(name),
+                                format!("conflicting schemas for property {name} in allOf"),
+                            ))?
+                        }
+                        Some(_) => {}
+                        None => {
+                            properties.insert(name, schema);
+                        }
+                    }
+                }
+            }
+            "required" => {
+                let serde_yaml::Value::Sequence(v) = v else {
+                    Err(invalid(pointer, "required must be a list".into()))?
+                };
+                for name in v {
+                    if !required.contains(&name) {
+                        required.push(name);
+                    }
+                }
+            }
+            "type" => Err(invalid(
+                pointer,
+                format!(
+                    "allOf parts must be object schemas, not {}",
+                    v.as_str().unwrap_or("a list of types")
+                ),
+            ))?,
+            key => Err(invalid(
+                pointer,
+                format!("unsupported keyword in allOf part: {key}"),
+            ))?,
+        }
+    }
+    Ok(())
+}
+
+/// The keywords that document a schema rather than constrain its values.
+const DOC_KEYWORDS: [&str; 4] = [
+    "title",
+    "description",
+    "deprecated",
+    "x-turbopuffer-deprecated-message",
+];
+
+/// Rewrites a `oneOf` without a `discriminator` as an `anyOf`, and an `anyOf`
+/// with a `discriminator` as a `oneOf`. Without a discriminator, the backends
+/// can't exploit the exclusivity of a `oneOf`'s variants, and with one, an
+/// `anyOf`'s variants are exclusive in practice.
+fn normalize_one_of(value: &mut serde_yaml::Value) {
+    let Some(map) = value.as_mapping_mut() else {
+        return;
+    };
+    let (from, to) = match map.contains_key("discriminator") {
+        false => ("oneOf", "anyOf"),
+        true => ("anyOf", "oneOf"),
+    };
+    if let Some(variants) = map.remove(from) {
+        map.insert(to.into(), variants);
+    }
+}
+
+/// Rewrites a JSON Schema string `enum`, along with the `x-enum-varnames`
+/// and `x-enum-descriptions` extensions, as an `anyOf` of `const`s, which is
+/// how [`OpenApiSchema`] represents enumerations.
+fn normalize_enum(value: &mut serde_yaml::Value, pointer: &Pointer) -> Result<(), Diagnostic> {
+    let Some(map) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+    let Some(values) = map.remove("enum") else {
+        return Ok(());
+    };
+    let invalid = |message: &str| diag(Code::InvalidSchema, pointer, message);
+
+    let serde_yaml::Value::Sequence(values) = values else {
+        Err(invalid("enum must be a list"))?
+    };
+    let mut nullable = match map.remove("type") {
+        Some(t) if t == "string" => false,
+        Some(serde_yaml::Value::Sequence(types))
+            if types.iter().all(|t| t == "string" || t == "null") =>
+        {
+            types.iter().any(|t| t == "null")
+        }
+        _ => Err(invalid("only enums of `type: string` are supported"))?,
+    };
+    let mut extension = |key: &str| match map.remove(key) {
+        None => Ok(vec![]),
+        Some(serde_yaml::Value::Sequence(entries)) if entries.len() == values.len() => Ok(entries),
+        Some(_) => Err(invalid(&format!(
+            "{key} must be a list with one entry per enum value"
+        ))),
+    };
+    let names = extension("x-enum-varnames")?;
+    let descriptions = extension("x-enum-descriptions")?;
+
+    let mut arms = vec![];
+    for (i, value) in values.into_iter().enumerate() {
+        match value {
+            serde_yaml::Value::Null => nullable = true,
+            serde_yaml::Value::String(_) => {
+                let mut arm = serde_yaml::Mapping::new();
+                arm.insert("const".into(), value);
+                if let Some(name) = names.get(i) {
+                    arm.insert("title".into(), name.clone());
+                }
+                if let Some(description) = descriptions.get(i) {
+                    arm.insert("description".into(), description.clone());
+                }
+                arms.push(serde_yaml::Value::Mapping(arm));
+            }
+            _ => Err(invalid("enum values must be strings"))?,
+        }
+    }
+    if nullable {
+        arms.push(null_schema());
+    }
+    map.insert("anyOf".into(), serde_yaml::Value::Sequence(arms));
+    Ok(())
+}
+
+/// Rewrites the JSON Schema 2020-12 `items` of a tuple, which gives the schema
+/// of the items after its `prefixItems`, as the older `additionalItems`, which
+/// is how [`OpenApiSchema`] spells it.
+///
+/// Also checks that the tuple gives its tail at all. JSON Schema allows any
+/// items after the `prefixItems` by default, but that's rarely what the spec
+/// means, so it must say so with `additionalItems: true`.
+fn normalize_tuple_items(
+    value: &mut serde_yaml::Value,
+    pointer: &Pointer,
+) -> Result<(), Diagnostic> {
+    let Some(map) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+    if !map.contains_key("prefixItems") {
+        return Ok(());
+    }
+    if !is_tuple_with_items(map) {
+        if !map.contains_key("additionalItems") {
+            Err(diag(
+                Code::UnsupportedTuple,
+                pointer,
+                "tuple doesn't say which items may follow its `prefixItems`; \
+                 set `additionalItems` to `false` for none, or to their schema",
+            ))?
+        }
+        return Ok(());
+    }
+    if map.contains_key("additionalItems") {
+        Err(diag(
+            Code::InvalidSchema,
+            pointer,
+            "tuple has both `items` and `additionalItems`",
+        ))?
+    }
+    rename_tuple_items(map);
+    Ok(())
+}
+
+fn is_tuple_with_items(map: &serde_yaml::Mapping) -> bool {
+    map.contains_key("prefixItems") && map.contains_key("items")
+}
+
+/// Renames the `items` of a tuple to `additionalItems`, in place.
+fn rename_tuple_items(map: &mut serde_yaml::Mapping) {
+    *map = std::mem::take(map)
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            Some("items") => ("additionalItems".into(), value),
+            _ => (key, value),
+        })
+        .collect();
+}
+
+/// Rewrites the OpenAPI 3.0 boolean `exclusiveMinimum` and `exclusiveMaximum`,
+/// which make `minimum` and `maximum` exclusive, as the OpenAPI 3.1 numeric
+/// form, which replaces them.
+fn normalize_exclusive_bounds(value: &mut serde_yaml::Value) {
+    let Some(map) = value.as_mapping_mut() else {
+        return;
+    };
+    for (exclusive, inclusive) in [
+        ("exclusiveMinimum", "minimum"),
+        ("exclusiveMaximum", "maximum"),
+    ] {
+        match map.get(exclusive).and_then(serde_yaml::Value::as_bool) {
+            Some(true) => {
+                if let Some(bound) = map.remove(inclusive) {
+                    map.insert(exclusive.into(), bound);
+                } else {
+                    map.remove(exclusive);
+                }
+            }
+            Some(false) => {
+                map.remove(exclusive);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Checks that no value of the format of an integer schema is ruled out by
+/// all of the schema's numeric bounds, e.g. that a `uint64` has no negative
+/// `maximum`, since the backends check the bounds against values of that
+/// format.
+fn check_integer_bounds(value: &serde_yaml::Value, pointer: &Pointer) -> Result<(), Diagnostic> {
+    let is_integer = match value.get("type") {
+        Some(serde_yaml::Value::Sequence(types)) => types.iter().any(|t| t == "integer"),
+        Some(t) => t == "integer",
+        None => false,
+    };
+    let format = value.get("format").and_then(serde_yaml::Value::as_str);
+    let range = IntegerFormat::from_name(format).and_then(IntegerFormat::range);
+    let (true, Some(range)) = (is_integer, range) else {
+        return Ok(());
+    };
+    let bounds = [
+        ("minimum", Constraint::Minimum as fn(_) -> _),
+        ("exclusiveMinimum", Constraint::ExclusiveMinimum),
+        ("maximum", Constraint::Maximum),
+        ("exclusiveMaximum", Constraint::ExclusiveMaximum),
+    ];
+    for (key, constraint) in bounds {
+        let Some(n) = value.get(key).and_then(serde_yaml::Value::as_f64) else {
+            continue;
+        };
+        if constraint(n).integral().satisfied_in(range) == Some(false) {
+            Err(diag(
+                Code::InvalidSchema,
+                &pointer.join(key),
+                format!(
+                    "no {} satisfies {key} {}",
+                    format.unwrap_or_default(),
+                    format_bound(n)
+                ),
+            ))?
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the `default` of a scalar schema is of one of the schema's
+/// types, and in the range of its integer format, since the backends render it
+/// as a literal of that type.
+fn check_default(value: &serde_yaml::Value, pointer: &Pointer) -> Result<(), Diagnostic> {
+    let (Some(default), Some(types)) = (value.get("default"), value.get("type")) else {
+        return Ok(());
+    };
+    let types = match types {
+        serde_yaml::Value::Sequence(types) => types.iter().collect(),
+        _ => vec![types],
+    };
+    let nullable = value.get("nullable").and_then(serde_yaml::Value::as_bool) == Some(true);
+    let matches = types.iter().any(|t| match t.as_str() {
+        Some("string") => default.is_string(),
+        Some("number") => default.is_number(),
+        Some("integer") => match value.get("format").and_then(serde_yaml::Value::as_str) {
+            Some("int32") => default.as_i64().is_some_and(|i| i32::try_from(i).is_ok()),
+            Some("int64") | None => default.is_i64(),
+            Some("uint64") => default.is_u64(),
+            // Deserialization rejects other formats.
+            Some(_) => true,
+        },
+        Some("boolean") => default.is_bool(),
+        Some("null") => default.is_null(),
+        // Other schemas can't have a `default`; deserialization rejects it.
+        _ => true,
+    }) || (nullable && default.is_null());
+    if !matches {
+        Err(diag(
+            Code::InvalidSchema,
+            &pointer.join("default"),
+            "default does not match the schema's type",
+        ))?
+    }
+    Ok(())
+}
+
+/// Rewrites the OpenAPI 3.0 `nullable: true` keyword and the OpenAPI 3.1
+/// `type: [T, "null"]` form as an `anyOf` with a `{type: "null"}` arm, which
+/// is how [`OpenApiSchema`] represents nullability.
+fn normalize_nullable(value: &mut serde_yaml::Value) {
+    let Some(map) = value.as_mapping_mut() else {
+        return;
+    };
+
+    let mut nullable = match map.remove("nullable") {
+        None => false,
+        Some(serde_yaml::Value::Bool(nullable)) => nullable,
+        // Leave malformed values for deserialization to reject.
+        Some(other) => {
+            map.insert("nullable".into(), other);
+            return;
+        }
+    };
+    if let Some(serde_yaml::Value::Sequence(types)) = map.get("type") {
+        let non_null: Vec<_> = types
+            .iter()
+            .filter(|t| t.as_str() != Some("null"))
+            .cloned()
+            .collect();
+        nullable |= !non_null.is_empty() && non_null.len() < types.len();
+        let ty = match non_null.len() {
+            // `type: ["null"]` is just the null schema.
+            0 => "null".into(),
+            1 => non_null[0].clone(),
+            _ => serde_yaml::Value::Sequence(non_null),
+        };
+        map.insert("type".into(), ty);
+    }
+    if !nullable {
+        return;
+    }
+
+    // An `anyOf` (e.g., from an `enum`) can simply gain another arm.
+    if let Some(serde_yaml::Value::Sequence(arms)) = map.get_mut("anyOf") {
+        arms.push(null_schema());
+        return;
+    }
+
+    // The title, description and deprecation name and document the value
+    // (e.g., a tuple field), so they belong on the nullable schema as a whole.
+    let mut outer = serde_yaml::Mapping::new();
+    for key in DOC_KEYWORDS {
+        if let Some(v) = map.remove(key) {
+            outer.insert(key.into(), v);
+        }
+    }
+    let inner = mem::take(value);
+    outer.insert(
+        "anyOf".into(),
+        serde_yaml::Value::Sequence(vec![inner, null_schema()]),
+    );
+    *value = serde_yaml::Value::Mapping(outer);
+}
+
+/// A form of [`OpenApiSchema`], described by its keys, for explaining why a
+/// schema matches none of them.
+struct SchemaForm {
+    /// The form's name, for messages.
+    name: &'static str,
+    /// The value of the `type` key, if the form has one.
+    ty: Option<&'static str>,
+    required: &'static [&'static str],
+    optional: &'static [&'static str],
+}
+
+impl SchemaForm {
+    fn supports(&self, key: &str) -> bool {
+        let common = ["title", "deprecated", "x-turbopuffer-deprecated-message"];
+        // References are described by the schema they refer to.
+        let described = self.required != ["$ref"] && key == "description";
+        (self.ty.is_some() && key == "type")
+            || described
+            || common.contains(&key)
+            || self.required.contains(&key)
+            || self.optional.contains(&key)
+    }
+}
+
+/// The forms of [`OpenApiSchema`], in the order of its variants, which this
+/// must be kept in sync with.
+const SCHEMA_FORMS: &[SchemaForm] = &[
+    SchemaForm {
+        name: "an `anyOf`",
+        ty: None,
+        required: &["anyOf"],
+        optional: &[],
+    },
+    SchemaForm {
+        name: "a `oneOf`",
+        ty: None,
+        required: &["oneOf", "discriminator"],
+        optional: &[],
+    },
+    SchemaForm {
+        name: "an object",
+        ty: Some("object"),
+        required: &["properties"],
+        optional: &["required"],
+    },
+    SchemaForm {
+        name: "a map",
+        ty: Some("object"),
+        required: &["additionalProperties"],
+        optional: &["propertyNames", "x-turbopuffer-variant-name"],
+    },
+    SchemaForm {
+        name: "an array",
+        ty: Some("array"),
+        required: &["items"],
+        optional: &["minItems", "maxItems"],
+    },
+    SchemaForm {
+        name: "a tuple",
+        ty: Some("array"),
+        required: &["prefixItems", "additionalItems"],
+        optional: &[
+            "x-turbopuffer-variant-name",
+            "x-turbopuffer-variant-drop-on-conflict",
+        ],
+    },
+    SchemaForm {
+        name: "a string",
+        ty: Some("string"),
+        required: &[],
+        optional: &[
+            "x-turbopuffer-variant-name",
+            "default",
+            "minLength",
+            "maxLength",
+            "pattern",
+        ],
+    },
+    SchemaForm {
+        name: "a number",
+        ty: Some("number"),
+        required: &[],
+        optional: &[
+            "default",
+            "minimum",
+            "exclusiveMinimum",
+            "maximum",
+            "exclusiveMaximum",
+            "x-turbopuffer-width",
+        ],
+    },
+    SchemaForm {
+        name: "an integer",
+        ty: Some("integer"),
+        required: &[],
+        optional: &[
+            "default",
+            "minimum",
+            "exclusiveMinimum",
+            "maximum",
+            "exclusiveMaximum",
+            "format",
+        ],
+    },
+    SchemaForm {
+        name: "a boolean",
+        ty: Some("boolean"),
+        required: &[],
+        optional: &["default"],
+    },
+    SchemaForm {
+        name: "a `const`",
+        ty: None,
+        required: &["const"],
+        optional: &[],
+    },
+    SchemaForm {
+        name: "a null",
+        ty: Some("null"),
+        required: &[],
+        optional: &[],
+    },
+    SchemaForm {
+        name: "a `$ref`",
+        ty: None,
+        required: &["$ref"],
+        optional: &[],
+    },
+    SchemaForm {
+        name: "an any-type schema",
+        ty: None,
+        required: &[],
+        optional: &["x-stainless-any"],
+    },
+];
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(untagged)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all_fields = "camelCase")]
 pub enum OpenApiSchema {
     AnyOf {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         any_of: Vec<OpenApiSchema>,
+        #[serde(rename = "title")]
+        title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+    },
+    /// A tagged union, whose variants are references to object schemas that
+    /// are told apart by the value of the `discriminator` property.
+    OneOf {
+        description: Option<String>,
+        one_of: Vec<OpenApiSchema>,
+        discriminator: Discriminator,
+        #[serde(rename = "title")]
+        title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     Object {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("object"),
         properties: BTreeMap<String, OpenApiSchema>,
@@ -72,10 +1253,13 @@ pub enum OpenApiSchema {
         required: Vec<String>,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     Map {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("object"),
         additional_properties: Box<OpenApiSchema>,
@@ -92,6 +1276,10 @@ pub enum OpenApiSchema {
         x_turbopuffer_variant_name: Option<String>,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     ArrayList {
         description: Option<String>,
@@ -100,18 +1288,28 @@ pub enum OpenApiSchema {
         items: Box<OpenApiSchema>,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+        min_items: Option<u64>,
+        max_items: Option<u64>,
     },
     ArrayTuple {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("array"),
-        /// XXX: this should be called `items` according to the current version
-        /// of the JSON Schema spec, but Stainless chokes on that. So we use
-        /// the older spelling of `additionalItems`, which Stainless just
-        /// ignores.
-        #[serde(default = "default_true")]
-        additional_items: bool,
+        /// The schema of the items after `prefix_items`, if the tuple has a
+        /// variadic tail. `true` allows any items, and `false` none.
+        /// Normalization rejects tuples that leave it out.
+        ///
+        /// The current version of the JSON Schema spec calls this `items`, but
+        /// Stainless chokes on that, so specs may use the older spelling of
+        /// `additionalItems`, which Stainless just ignores. Normalization
+        /// accepts both; see [`stainless_spec`] for converting from one to the
+        /// other.
+        #[serde(deserialize_with = "deserialize_additional_items")]
+        additional_items: Option<Box<OpenApiSchema>>,
         prefix_items: Vec<OpenApiSchema>,
         /// When used in an `anyOf` schema, the name to use for the variant (if
         /// the target language requires variants to be explicitly named). If
@@ -130,10 +1328,13 @@ pub enum OpenApiSchema {
         x_turbopuffer_variant_drop_on_conflict: bool,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     String {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("string"),
         /// When used in an `anyOf` schema, the name to use for the variant (if
@@ -142,57 +1343,125 @@ pub enum OpenApiSchema {
         x_turbopuffer_variant_name: Option<String>,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+        default: Option<ConstValue>,
+        min_length: Option<u64>,
+        max_length: Option<u64>,
+        pattern: Option<String>,
     },
     Number {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("number"),
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+        default: Option<ConstValue>,
+        minimum: Option<f64>,
+        /// Normalized to the OpenAPI 3.1 form, a number, by
+        /// [`normalize_exclusive_bounds`].
+        exclusive_minimum: Option<f64>,
+        maximum: Option<f64>,
+        /// Normalized like `exclusive_minimum`.
+        exclusive_maximum: Option<f64>,
         #[serde(rename = "x-turbopuffer-width")]
         x_turbopuffer_width: Option<u32>,
     },
+    Integer {
+        description: Option<String>,
+        #[serde(rename = "type")]
+        _type: MustBe!("integer"),
+        #[serde(rename = "title")]
+        title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+        default: Option<ConstValue>,
+        minimum: Option<f64>,
+        /// Normalized to the OpenAPI 3.1 form, a number, by
+        /// [`normalize_exclusive_bounds`].
+        exclusive_minimum: Option<f64>,
+        maximum: Option<f64>,
+        /// Normalized like `exclusive_minimum`.
+        exclusive_maximum: Option<f64>,
+        /// One of `int32`, `int64` or `uint64`. See [`IntegerFormat`].
+        format: Option<String>,
+    },
     Boolean {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "type")]
         _type: MustBe!("boolean"),
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+        default: Option<ConstValue>,
     },
     Const {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "const")]
-        sconst: String,
+        value: ConstValue,
+        #[serde(rename = "title")]
+        title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
+    },
+    Null {
+        description: Option<String>,
+        #[serde(rename = "type")]
+        _type: MustBe!("null"),
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     Ref {
         #[serde(rename = "$ref")]
         sref: String,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
     Any {
-        #[serde(rename = "description")]
-        _description: Option<String>,
+        description: Option<String>,
         #[serde(rename = "x-stainless-any")]
         _x_stainless_any: Option<MustBe!(true)>,
         #[serde(rename = "title")]
         title: Option<String>,
+        #[serde(default)]
+        deprecated: bool,
+        #[serde(rename = "x-turbopuffer-deprecated-message")]
+        x_turbopuffer_deprecated_message: Option<String>,
     },
 }
 
 impl OpenApiSchema {
     pub fn title(&self) -> Option<&str> {
         match self {
-            OpenApiSchema::AnyOf { .. } => None,
-            OpenApiSchema::String { title, .. }
+            OpenApiSchema::AnyOf { title, .. }
+            | OpenApiSchema::OneOf { title, .. }
+            | OpenApiSchema::String { title, .. }
             | OpenApiSchema::Boolean { title, .. }
             | OpenApiSchema::Number { title, .. }
+            | OpenApiSchema::Integer { title, .. }
             | OpenApiSchema::Const { title, .. }
+            | OpenApiSchema::Null { title, .. }
             | OpenApiSchema::Ref { title, .. }
             | OpenApiSchema::Any { title, .. }
             | OpenApiSchema::ArrayTuple { title, .. }
@@ -204,11 +1473,14 @@ impl OpenApiSchema {
 
     pub fn title_mut(&mut self) -> Option<&mut Option<String>> {
         match self {
-            OpenApiSchema::AnyOf { .. } => None,
-            OpenApiSchema::String { title, .. }
+            OpenApiSchema::AnyOf { title, .. }
+            | OpenApiSchema::OneOf { title, .. }
+            | OpenApiSchema::String { title, .. }
             | OpenApiSchema::Boolean { title, .. }
             | OpenApiSchema::Number { title, .. }
+            | OpenApiSchema::Integer { title, .. }
             | OpenApiSchema::Const { title, .. }
+            | OpenApiSchema::Null { title, .. }
             | OpenApiSchema::Ref { title, .. }
             | OpenApiSchema::Any { title, .. }
             | OpenApiSchema::ArrayTuple { title, .. }
@@ -218,6 +1490,218 @@ impl OpenApiSchema {
         }
     }
 
+    /// Returns the schema's description, for the doc comments of the code
+    /// generated for it.
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            OpenApiSchema::AnyOf { description, .. }
+            | OpenApiSchema::OneOf { description, .. }
+            | OpenApiSchema::String { description, .. }
+            | OpenApiSchema::Boolean { description, .. }
+            | OpenApiSchema::Number { description, .. }
+            | OpenApiSchema::Integer { description, .. }
+            | OpenApiSchema::Const { description, .. }
+            | OpenApiSchema::Null { description, .. }
+            | OpenApiSchema::Any { description, .. }
+            | OpenApiSchema::ArrayTuple { description, .. }
+            | OpenApiSchema::Object { description, .. }
+            | OpenApiSchema::Map { description, .. } => description.as_deref(),
+            // The description of a list of `any` may hold its generic type
+            // parameter instead. See `shared::assign_generics`.
+            OpenApiSchema::ArrayList { description, .. } => {
+                match shared::array_list_generic(description) {
+                    Some(_) => None,
+                    None => description.as_deref(),
+                }
+            }
+            OpenApiSchema::Ref { .. } => None,
+        }
+    }
+
+    /// Returns the deprecation message of the schema, if it is `deprecated`.
+    /// A tuple is also deprecated if any of its `const` items is, as those
+    /// spell the operator that the tuple stands for.
+    pub fn deprecated(&self) -> Option<&str> {
+        let (deprecated, message) = match self {
+            OpenApiSchema::AnyOf {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::OneOf {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Object {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Map {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::ArrayList {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::String {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Number {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Integer {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Boolean {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Const {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Null {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Ref {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            }
+            | OpenApiSchema::Any {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            } => (*deprecated, x_turbopuffer_deprecated_message),
+            OpenApiSchema::ArrayTuple {
+                deprecated: false,
+                prefix_items,
+                ..
+            } => {
+                return prefix_items
+                    .iter()
+                    .filter(|item| matches!(item, OpenApiSchema::Const { .. }))
+                    .find_map(OpenApiSchema::deprecated);
+            }
+            OpenApiSchema::ArrayTuple {
+                deprecated,
+                x_turbopuffer_deprecated_message,
+                ..
+            } => (*deprecated, x_turbopuffer_deprecated_message),
+        };
+        match deprecated {
+            true => Some(message.as_deref().unwrap_or(DEFAULT_DEPRECATION_MESSAGE)),
+            false => None,
+        }
+    }
+
+    /// Returns the schema's validation constraints, looking through
+    /// nullability like [`OpenApiSchema::default`].
+    pub fn constraints(&self) -> Vec<Constraint<'_>> {
+        match self {
+            OpenApiSchema::ArrayList {
+                min_items,
+                max_items,
+                ..
+            } => [
+                min_items.map(Constraint::MinItems),
+                max_items.map(Constraint::MaxItems),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            OpenApiSchema::String {
+                min_length,
+                max_length,
+                pattern,
+                ..
+            } => [
+                min_length.map(Constraint::MinLength),
+                max_length.map(Constraint::MaxLength),
+                pattern.as_deref().map(Constraint::Pattern),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            OpenApiSchema::Number {
+                minimum,
+                exclusive_minimum,
+                maximum,
+                exclusive_maximum,
+                ..
+            } => [
+                minimum.map(Constraint::Minimum),
+                exclusive_minimum.map(Constraint::ExclusiveMinimum),
+                maximum.map(Constraint::Maximum),
+                exclusive_maximum.map(Constraint::ExclusiveMaximum),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            OpenApiSchema::Integer {
+                minimum,
+                exclusive_minimum,
+                maximum,
+                exclusive_maximum,
+                format,
+                ..
+            } => {
+                let range =
+                    IntegerFormat::from_name(format.as_deref()).and_then(IntegerFormat::range);
+                // Bounds that every value of the format satisfies need no
+                // check. Those that none does were rejected by
+                // `check_integer_bounds`.
+                [
+                    minimum.map(Constraint::Minimum),
+                    exclusive_minimum.map(Constraint::ExclusiveMinimum),
+                    maximum.map(Constraint::Maximum),
+                    exclusive_maximum.map(Constraint::ExclusiveMaximum),
+                ]
+                .into_iter()
+                .flatten()
+                .map(Constraint::integral)
+                .filter(|c| range.is_none_or(|range| c.satisfied_in(range) != Some(true)))
+                .collect()
+            }
+            OpenApiSchema::AnyOf { any_of, .. } => match any_of.as_slice() {
+                [schema, OpenApiSchema::Null { .. }] => schema.constraints(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Returns the schema's `default` value, which only scalar schemas, and
+    /// nullable scalar schemas, can have.
+    pub fn default(&self) -> Option<&ConstValue> {
+        match self {
+            OpenApiSchema::String { default, .. }
+            | OpenApiSchema::Number { default, .. }
+            | OpenApiSchema::Integer { default, .. }
+            | OpenApiSchema::Boolean { default, .. } => default.as_ref(),
+            OpenApiSchema::AnyOf { any_of, .. } => match any_of.as_slice() {
+                [schema, OpenApiSchema::Null { .. }] => schema.default(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     fn collect_refs(&self, refs: &mut BTreeSet<String>) {
         match self {
             OpenApiSchema::Ref { sref, .. } => {
@@ -225,8 +1709,9 @@ impl OpenApiSchema {
                     refs.insert(name.to_string());
                 }
             }
-            OpenApiSchema::AnyOf { any_of, .. } => {
-                for schema in any_of {
+            OpenApiSchema::AnyOf { any_of: arms, .. }
+            | OpenApiSchema::OneOf { one_of: arms, .. } => {
+                for schema in arms {
                     schema.collect_refs(refs);
                 }
             }
@@ -248,25 +1733,853 @@ impl OpenApiSchema {
             OpenApiSchema::ArrayList { items, .. } => {
                 items.collect_refs(refs);
             }
-            OpenApiSchema::ArrayTuple { prefix_items, .. } => {
+            OpenApiSchema::ArrayTuple {
+                prefix_items,
+                additional_items,
+                ..
+            } => {
                 for schema in prefix_items {
                     schema.collect_refs(refs);
                 }
+                if let Some(additional_items) = additional_items {
+                    additional_items.collect_refs(refs);
+                }
             }
             OpenApiSchema::String { .. }
             | OpenApiSchema::Number { .. }
+            | OpenApiSchema::Integer { .. }
             | OpenApiSchema::Boolean { .. }
             | OpenApiSchema::Const { .. }
+            | OpenApiSchema::Null { .. }
             | OpenApiSchema::Any { .. } => {}
         }
     }
+
+    /// If the schema is an `anyOf` with a `null` arm, returns the schema
+    /// without that arm: the sole remaining arm, if there is only one, or
+    /// else an `anyOf` of the remaining arms.
+    pub fn without_null(&self) -> Option<OpenApiSchema> {
+        let OpenApiSchema::AnyOf {
+            description,
+            any_of,
+            title,
+            deprecated,
+            x_turbopuffer_deprecated_message,
+        } = self
+        else {
+            return None;
+        };
+        let is_null = |s: &OpenApiSchema| matches!(s, OpenApiSchema::Null { .. });
+        if !any_of.iter().any(is_null) {
+            return None;
+        }
+        let mut rest: Vec<_> = any_of.iter().filter(|s| !is_null(s)).cloned().collect();
+        match rest.len() {
+            0 => None,
+            1 => Some(rest.remove(0)),
+            _ => Some(OpenApiSchema::AnyOf {
+                description: description.clone(),
+                any_of: rest,
+                title: title.clone(),
+                deprecated: *deprecated,
+                x_turbopuffer_deprecated_message: x_turbopuffer_deprecated_message.clone(),
+            }),
+        }
+    }
+}
+
+/// The deprecation message of a schema that is `deprecated` without an
+/// `x-turbopuffer-deprecated-message`.
+const DEFAULT_DEPRECATION_MESSAGE: &str = "Will be removed in a future version.";
+
+/// The OpenAPI `discriminator` of a `oneOf` schema.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct Discriminator {
+    /// The name of the property whose value identifies the variant.
+    pub property_name: String,
+    /// Maps property values to the variant schemas they identify, by name or
+    /// by reference. Variants that are not mapped are identified by the value
+    /// of their `const` discriminator property, if any, or else by their
+    /// schema name.
+    #[serde(default)]
+    pub mapping: BTreeMap<String, String>,
+}
+
+/// The value of a `const` schema.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ConstValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    /// An integer above `i64::MAX`.
+    UInteger(u64),
+    Number(f64),
+    String(String),
+}
+
+impl ConstValue {
+    /// Returns the value if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConstValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a number, integer or not.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ConstValue::Integer(i) => Some(*i as f64),
+            ConstValue::UInteger(u) => Some(*u as f64),
+            ConstValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    /// Formats the value as JSON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::Null => f.write_str("null"),
+            ConstValue::Bool(b) => write!(f, "{b}"),
+            ConstValue::Integer(i) => write!(f, "{i}"),
+            ConstValue::UInteger(u) => write!(f, "{u}"),
+            // `Debug` keeps the decimal point on whole numbers, so that every
+            // language reads the literal back as a floating point number.
+            ConstValue::Number(n) => write!(f, "{n:?}"),
+            ConstValue::String(s) => write!(f, "{}", serde_json::Value::from(s.as_str())),
+        }
+    }
+}
+
+/// A validation constraint of a schema, which the constructors generated for
+/// the schema check.
+#[derive(Debug, Clone, Copy)]
+pub enum Constraint<'a> {
+    MinItems(u64),
+    MaxItems(u64),
+    /// In Unicode code points.
+    MinLength(u64),
+    MaxLength(u64),
+    Minimum(f64),
+    ExclusiveMinimum(f64),
+    Maximum(f64),
+    ExclusiveMaximum(f64),
+    /// A regular expression that matches anywhere in the string, unless
+    /// anchored.
+    Pattern(&'a str),
+}
+
+impl Constraint<'_> {
+    /// Returns the error message for a `name` that violates the constraint.
+    pub fn message(&self, name: &str) -> String {
+        let plural = |n: &u64| if *n == 1 { "" } else { "s" };
+        match self {
+            Constraint::MinItems(n) => format!("{name} must have at least {n} item{}", plural(n)),
+            Constraint::MaxItems(n) => format!("{name} must have at most {n} item{}", plural(n)),
+            Constraint::MinLength(n) => {
+                format!("{name} must be at least {n} character{} long", plural(n))
+            }
+            Constraint::MaxLength(n) => {
+                format!("{name} must be at most {n} character{} long", plural(n))
+            }
+            Constraint::Minimum(n) => format!("{name} must be at least {}", format_bound(*n)),
+            Constraint::ExclusiveMinimum(n) => {
+                format!("{name} must be greater than {}", format_bound(*n))
+            }
+            Constraint::Maximum(n) => format!("{name} must be at most {}", format_bound(*n)),
+            Constraint::ExclusiveMaximum(n) => {
+                format!("{name} must be less than {}", format_bound(*n))
+            }
+            Constraint::Pattern(pattern) => format!("{name} must match {pattern}"),
+        }
+    }
+}
+
+impl<'a> Constraint<'a> {
+    /// Returns the equivalent of a numeric bound for integers, which is an
+    /// integer itself, e.g. `minimum: 1` for `exclusiveMinimum: 0.5`.
+    fn integral(self) -> Constraint<'a> {
+        match self {
+            Constraint::Minimum(n) => Constraint::Minimum(n.ceil()),
+            Constraint::ExclusiveMinimum(n) if n.fract() != 0.0 => Constraint::Minimum(n.ceil()),
+            Constraint::Maximum(n) => Constraint::Maximum(n.floor()),
+            Constraint::ExclusiveMaximum(n) if n.fract() != 0.0 => Constraint::Maximum(n.floor()),
+            constraint => constraint,
+        }
+    }
+
+    /// Returns whether every integer in the inclusive `range` satisfies the
+    /// [`integral`](Constraint::integral) numeric bound (`Some(true)`), or
+    /// none does (`Some(false)`). Returns `None` if only some do, or if the
+    /// constraint isn't a numeric bound.
+    fn satisfied_in(&self, (min, max): (i128, i128)) -> Option<bool> {
+        // Integral bounds convert exactly, or saturate well outside of any
+        // range.
+        let (all, none) = match *self {
+            Constraint::Minimum(n) => (n as i128 <= min, n as i128 > max),
+            Constraint::ExclusiveMinimum(n) => ((n as i128) < min, n as i128 >= max),
+            Constraint::Maximum(n) => (n as i128 >= max, (n as i128) < min),
+            Constraint::ExclusiveMaximum(n) => (n as i128 > max, n as i128 <= min),
+            _ => (false, false),
+        };
+        match (all, none) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Formats a numeric bound like JSON, without a fractional part if it has
+/// none, so that it's a valid literal for integers and floats alike in most
+/// languages.
+pub fn format_bound(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{n:?}")
+    }
+}
+
+/// The range of an integer schema, as given by its `format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerFormat {
+    /// No format given. Backends use their language's natural integer type.
+    Unspecified,
+    Int32,
+    Int64,
+    Uint64,
+}
+
+impl IntegerFormat {
+    pub fn parse(format: Option<&str>, pointer: &Pointer) -> Result<IntegerFormat, Diagnostic> {
+        IntegerFormat::from_name(format).ok_or_else(|| {
+            diag(
+                Code::UnsupportedIntegerFormat,
+                pointer,
+                format!("unsupported integer format: {}", format.unwrap_or_default()),
+            )
+        })
+    }
+
+    /// Returns the format with the given name, if it's supported.
+    fn from_name(format: Option<&str>) -> Option<IntegerFormat> {
+        match format {
+            None => Some(IntegerFormat::Unspecified),
+            Some("int32") => Some(IntegerFormat::Int32),
+            Some("int64") => Some(IntegerFormat::Int64),
+            Some("uint64") => Some(IntegerFormat::Uint64),
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the smallest and largest values of the format, unless it's up
+    /// to the backends.
+    fn range(self) -> Option<(i128, i128)> {
+        match self {
+            IntegerFormat::Unspecified => None,
+            IntegerFormat::Int32 => Some((i32::MIN.into(), i32::MAX.into())),
+            IntegerFormat::Int64 => Some((i64::MIN.into(), i64::MAX.into())),
+            IntegerFormat::Uint64 => Some((0, u64::MAX.into())),
+        }
+    }
+}
+
+fn null_schema() -> serde_yaml::Value {
+    let mut map = serde_yaml::Mapping::new();
+    map.insert("type".into(), "null".into());
+    serde_yaml::Value::Mapping(map)
+}
+
+fn any_items() -> Option<Box<OpenApiSchema>> {
+    Some(Box::new(OpenApiSchema::Any {
+        description: None,
+        _x_stainless_any: None,
+        title: None,
+        deprecated: false,
+        x_turbopuffer_deprecated_message: None,
+    }))
+}
+
+fn deserialize_additional_items<'de, D>(
+    deserializer: D,
+) -> Result<Option<Box<OpenApiSchema>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum AdditionalItems {
+        Bool(bool),
+        Schema(Box<OpenApiSchema>),
+    }
+
+    match AdditionalItems::deserialize(deserializer)? {
+        AdditionalItems::Bool(true) => Ok(any_items()),
+        AdditionalItems::Bool(false) => Ok(None),
+        AdditionalItems::Schema(schema) => Ok(Some(schema)),
+    }
 }
 
-fn default_true() -> bool {
-    true
+fn strip_schema_ref_prefix<'a>(sref: &'a str, pointer: &Pointer) -> Result<&'a str, Diagnostic> {
+    sref.strip_prefix(SCHEMA_REF_PREFIX).ok_or_else(|| {
+        diag(
+            Code::UnsupportedReference,
+            pointer,
+            format!("unsupported reference: {sref}"),
+        )
+    })
 }
 
-fn strip_schema_ref_prefix(sref: &str) -> Result<&str, Box<dyn Error>> {
-    sref.strip_prefix(SCHEMA_REF_PREFIX)
-        .ok_or_else(|| format!("unsupported reference: {sref}").into())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    fn pointer() -> Pointer {
+        Pointer::schema("Test")
+    }
+
+    /// Parses a spec with the given `#/components/schemas`, all managed.
+    pub(super) fn parse_schemas(schemas: &str) -> Result<OpenApiSpec, Box<dyn Error>> {
+        let mut content = String::from("components:\n  schemas:\n");
+        for line in schemas.lines() {
+            content.push_str(&format!("    {line}\n"));
+        }
+        let selector = SchemaSelector {
+            prefixes: vec![String::new()],
+            ..SchemaSelector::default()
+        };
+        OpenApiSpec::parse(&content, None, &selector)
+    }
+
+    /// Consts that each backend must render exactly: an integer above
+    /// `i64::MAX`, a negative one, and a string with characters that need
+    /// escaping.
+    pub(super) const CONSTS: &str = r#"
+FilterBig:
+  type: array
+  prefixItems:
+    - {type: string, title: attr}
+    - const: 18446744073709551615
+    - const: -3
+  additionalItems: false
+FilterMsg:
+  type: object
+  properties:
+    op: {const: "a\nb\t$c\"\r"}
+  required: [op]
+"#;
+
+    /// A `uint64` with bounds that make it at least 1, given as an inclusive
+    /// bound that every `uint64` satisfies and an exclusive one that isn't
+    /// an integer, and at most 100.
+    pub(super) const UINT64: &str = "
+FilterCount:
+  type: array
+  prefixItems:
+    - {type: string, title: attr}
+    - {const: Count}
+    - {type: integer, format: uint64, title: n, minimum: 0, exclusiveMinimum: 0.5, maximum: 100}
+  additionalItems: false
+";
+
+    /// A backend's `render` function.
+    type Render = fn(
+        OpenApiSpec,
+        &TargetOptions,
+        &mut Diagnostics,
+    ) -> Result<crate::util::codegen_buf::CodegenBuf, Box<dyn Error>>;
+
+    /// Renders the given `#/components/schemas` with `render`, failing with
+    /// the diagnostics if there are any.
+    pub(super) fn render_schemas(render: Render, schemas: &str) -> Result<String, Diagnostics> {
+        render_schemas_with(render, &TargetOptions::default(), schemas)
+    }
+
+    /// Like [`render_schemas`], but with the given target options.
+    pub(super) fn render_schemas_with(
+        render: Render,
+        options: &TargetOptions,
+        schemas: &str,
+    ) -> Result<String, Diagnostics> {
+        let spec = parse_schemas(schemas).map_err(Diagnostics::from_error)?;
+        let mut diagnostics = Diagnostics::default();
+        let buf = render(spec, options, &mut diagnostics).map_err(Diagnostics::from_error)?;
+        match diagnostics.is_empty() {
+            true => Ok(buf.into_string()),
+            false => Err(diagnostics),
+        }
+    }
+
+    #[test]
+    fn normalize_nullable_keyword() {
+        let mut value = yaml("{type: string, nullable: true, description: d}");
+        normalize_nullable(&mut value);
+        assert_eq!(
+            value,
+            yaml("{description: d, anyOf: [{type: string}, {type: 'null'}]}")
+        );
+
+        let mut value = yaml("{type: string, nullable: false}");
+        normalize_nullable(&mut value);
+        assert_eq!(value, yaml("{type: string}"));
+    }
+
+    #[test]
+    fn normalize_nullable_type_list() {
+        let mut value = yaml("{type: [integer, 'null'], format: int32}");
+        normalize_nullable(&mut value);
+        assert_eq!(
+            value,
+            yaml("{anyOf: [{type: integer, format: int32}, {type: 'null'}]}")
+        );
+
+        let mut value = yaml("{type: ['null']}");
+        normalize_nullable(&mut value);
+        assert_eq!(value, yaml("{type: 'null'}"));
+
+        let mut value = yaml("{type: [string, number]}");
+        normalize_nullable(&mut value);
+        assert_eq!(value, yaml("{type: [string, number]}"));
+    }
+
+    #[test]
+    fn normalize_nullable_any_of() {
+        let mut value = yaml("{anyOf: [{const: a}], nullable: true}");
+        normalize_nullable(&mut value);
+        assert_eq!(value, yaml("{anyOf: [{const: a}, {type: 'null'}]}"));
+    }
+
+    #[test]
+    fn normalize_nullable_leaves_malformed_values() {
+        let mut value = yaml("{type: string, nullable: yes please}");
+        normalize_nullable(&mut value);
+        assert_eq!(value, yaml("{type: string, nullable: yes please}"));
+    }
+
+    #[test]
+    fn normalize_enum_as_consts() {
+        let mut value = yaml(
+            "
+type: string
+enum: [asc, desc]
+x-enum-varnames: [Ascending, Descending]
+x-enum-descriptions: [Smallest first., Largest first.]
+description: An order.
+",
+        );
+        normalize_enum(&mut value, &pointer()).unwrap();
+        assert_eq!(
+            value,
+            yaml(
+                "
+description: An order.
+anyOf:
+  - {const: asc, title: Ascending, description: Smallest first.}
+  - {const: desc, title: Descending, description: Largest first.}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_enum_with_null() {
+        for schema in [
+            "{type: string, enum: [a, null]}",
+            "{type: [string, 'null'], enum: [a]}",
+        ] {
+            let mut value = yaml(schema);
+            normalize_enum(&mut value, &pointer()).unwrap();
+            assert_eq!(value, yaml("{anyOf: [{const: a}, {type: 'null'}]}"));
+        }
+    }
+
+    #[test]
+    fn normalize_enum_rejects_unsupported_enums() {
+        for (schema, message) in [
+            ("{enum: a}", "enum must be a list"),
+            (
+                "{type: integer, enum: [1]}",
+                "only enums of `type: string` are supported",
+            ),
+            ("{type: string, enum: [1]}", "enum values must be strings"),
+            (
+                "{type: string, enum: [a, b], x-enum-varnames: [A]}",
+                "x-enum-varnames must be a list with one entry per enum value",
+            ),
+        ] {
+            let err = normalize_enum(&mut yaml(schema), &pointer()).unwrap_err();
+            assert_eq!(err, diag(Code::InvalidSchema, &pointer(), message));
+        }
+    }
+
+    #[test]
+    fn normalize_one_of_by_discriminator() {
+        let mut value = yaml("{oneOf: [{type: string}]}");
+        normalize_one_of(&mut value);
+        assert_eq!(value, yaml("{anyOf: [{type: string}]}"));
+
+        let mut value = yaml("{anyOf: [{$ref: A}], discriminator: {propertyName: kind}}");
+        normalize_one_of(&mut value);
+        assert_eq!(
+            value,
+            yaml("{discriminator: {propertyName: kind}, oneOf: [{$ref: A}]}")
+        );
+
+        let mut value = yaml("{oneOf: [{$ref: A}], discriminator: {propertyName: kind}}");
+        normalize_one_of(&mut value);
+        assert_eq!(
+            value,
+            yaml("{oneOf: [{$ref: A}], discriminator: {propertyName: kind}}")
+        );
+    }
+
+    fn normalize_all_of_in(schemas: &str, schema: &str) -> Result<serde_yaml::Value, Diagnostic> {
+        let schemas = yaml(schemas);
+        let mut components = Components {
+            schemas: schemas.as_mapping().unwrap(),
+            resolving: vec!["Test".into()],
+        };
+        let mut value = yaml(schema);
+        normalize_all_of(&mut value, &pointer(), &mut components)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn normalize_all_of_merges_objects() {
+        let value = normalize_all_of_in(
+            "
+Base:
+  type: object
+  properties:
+    id: {type: string, description: The ID.}
+  required: [id]
+",
+            "
+description: Derived.
+allOf:
+  - $ref: '#/components/schemas/Base'
+  - type: object
+    properties:
+      id: {type: string}
+      name: {type: string}
+    required: [name, id]
+properties:
+  extra: {type: boolean}
+",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            yaml(
+                "
+description: Derived.
+type: object
+properties:
+  id: {type: string, description: The ID.}
+  name: {type: string}
+  extra: {type: boolean}
+required: [id, name]
+"
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_all_of_rejects_conflicts() {
+        let err = normalize_all_of_in(
+            "{}",
+            "
+allOf:
+  - properties: {id: {type: string}}
+  - properties: {id: {type: number}}
+",
+        )
+        .unwrap_err();
+        let pointer = pointer()
+            .join("allOf")
+            .join(1)
+            .join("properties")
+            .join("id");
+        assert_eq!(
+            err,
+            diag(
+                Code::InvalidSchema,
+                &pointer,
+                "conflicting schemas for property id in allOf"
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_all_of_rejects_non_objects() {
+        let err = normalize_all_of_in("{}", "{allOf: [{type: string}]}").unwrap_err();
+        assert_eq!(
+            err,
+            diag(
+                Code::InvalidSchema,
+                &pointer().join("allOf").join(0),
+                "allOf parts must be object schemas, not string"
+            )
+        );
+    }
+
+    #[test]
+    fn normalize_all_of_rejects_cycles() {
+        let err = normalize_all_of_in(
+            "Test: {allOf: [{$ref: '#/components/schemas/Test'}]}",
+            "{allOf: [{$ref: '#/components/schemas/Test'}]}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            diag(
+                Code::InvalidSchema,
+                &pointer().join("allOf").join(0),
+                "allOf refers back to Test"
+            )
+        );
+    }
+
+    #[test]
+    fn check_default_matches_types() {
+        for schema in [
+            "{type: string, default: a}",
+            "{type: number, default: 1}",
+            "{type: number, default: 1.5}",
+            "{type: integer, default: -1}",
+            "{type: integer, format: int32, default: 2147483647}",
+            "{type: integer, format: uint64, default: 18446744073709551615}",
+            "{type: boolean, default: false}",
+            "{type: [string, 'null'], default: null}",
+            "{type: string, nullable: true, default: null}",
+        ] {
+            check_default(&yaml(schema), &pointer()).unwrap();
+        }
+    }
+
+    #[test]
+    fn check_default_rejects_mismatches() {
+        for schema in [
+            "{type: string, default: 1}",
+            "{type: number, default: '1'}",
+            "{type: integer, default: 1.5}",
+            "{type: integer, default: 18446744073709551615}",
+            "{type: integer, format: int32, default: 2147483648}",
+            "{type: integer, format: uint64, default: -1}",
+            "{type: boolean, default: null}",
+        ] {
+            let err = check_default(&yaml(schema), &pointer()).unwrap_err();
+            assert_eq!(
+                err,
+                diag(
+                    Code::InvalidSchema,
+                    &pointer().join("default"),
+                    "default does not match the schema's type"
+                ),
+                "{schema}"
+            );
+        }
+    }
+
+    #[test]
+    fn integers_above_i64_max_are_exact() {
+        let value: ConstValue = serde_yaml::from_str("18446744073709551615").unwrap();
+        assert_eq!(value, ConstValue::UInteger(u64::MAX));
+        assert_eq!(value.to_string(), "18446744073709551615");
+        let value: ConstValue = serde_yaml::from_str("-1").unwrap();
+        assert_eq!(value, ConstValue::Integer(-1));
+    }
+
+    #[test]
+    fn check_integer_bounds_rejects_unsatisfiable_bounds() {
+        for schema in [
+            "{type: integer, format: uint64, minimum: 0, exclusiveMinimum: -1}",
+            "{type: integer, format: uint64, maximum: 0, exclusiveMaximum: 0.5}",
+            "{type: integer, format: int32, maximum: 3000000000}",
+            "{type: integer, maximum: -1}",
+            "{type: number, format: uint64, maximum: -1}",
+        ] {
+            check_integer_bounds(&yaml(schema), &pointer()).unwrap();
+        }
+        for (schema, key, message) in [
+            (
+                "{type: integer, format: uint64, maximum: -1}",
+                "maximum",
+                "no uint64 satisfies maximum -1",
+            ),
+            (
+                "{type: [integer, 'null'], format: uint64, exclusiveMaximum: 0}",
+                "exclusiveMaximum",
+                "no uint64 satisfies exclusiveMaximum 0",
+            ),
+            (
+                "{type: integer, format: uint64, maximum: -0.5}",
+                "maximum",
+                "no uint64 satisfies maximum -0.5",
+            ),
+            (
+                "{type: integer, format: int32, minimum: 2147483648}",
+                "minimum",
+                "no int32 satisfies minimum 2147483648",
+            ),
+        ] {
+            let err = check_integer_bounds(&yaml(schema), &pointer()).unwrap_err();
+            assert_eq!(
+                err,
+                diag(Code::InvalidSchema, &pointer().join(key), message),
+                "{schema}"
+            );
+        }
+    }
+
+    #[test]
+    fn integer_constraints_are_integral_and_necessary() {
+        let schema: OpenApiSchema = serde_yaml::from_str(
+            "{type: integer, format: uint64, minimum: 0, exclusiveMinimum: 0.5, \
+             maximum: 18446744073709551615, exclusiveMaximum: 10}",
+        )
+        .unwrap();
+        let messages: Vec<_> = schema
+            .constraints()
+            .iter()
+            .map(|c| c.message("n"))
+            .collect();
+        assert_eq!(messages, ["n must be at least 1", "n must be less than 10"]);
+
+        let schema: OpenApiSchema =
+            serde_yaml::from_str("{type: integer, minimum: 0, maximum: 9.5}").unwrap();
+        let messages: Vec<_> = schema
+            .constraints()
+            .iter()
+            .map(|c| c.message("n"))
+            .collect();
+        assert_eq!(messages, ["n must be at least 0", "n must be at most 9"]);
+    }
+
+    #[test]
+    fn normalize_exclusive_bounds_of_openapi_30() {
+        for (schema, normalized) in [
+            (
+                "{type: number, minimum: 0, exclusiveMinimum: true}",
+                "{type: number, exclusiveMinimum: 0}",
+            ),
+            (
+                "{type: number, maximum: 1, exclusiveMaximum: false}",
+                "{type: number, maximum: 1}",
+            ),
+            ("{type: number, exclusiveMaximum: true}", "{type: number}"),
+            (
+                "{type: number, minimum: 0, exclusiveMinimum: 1}",
+                "{type: number, minimum: 0, exclusiveMinimum: 1}",
+            ),
+        ] {
+            let mut value = yaml(schema);
+            normalize_exclusive_bounds(&mut value);
+            assert_eq!(value, yaml(normalized), "{schema}");
+        }
+    }
+
+    #[test]
+    fn tuples_must_give_their_tail() {
+        let mut value = yaml("{type: array, prefixItems: [{const: Sum}]}");
+        let err = normalize_tuple_items(&mut value, &pointer()).unwrap_err();
+        assert_eq!(err.code, Code::UnsupportedTuple);
+        assert_eq!(err.pointer, Some(pointer()));
+
+        for tail in ["true", "false", "{type: number}"] {
+            let schema =
+                format!("{{type: array, prefixItems: [{{const: Sum}}], additionalItems: {tail}}}");
+            let mut value = yaml(&schema);
+            normalize_tuple_items(&mut value, &pointer()).unwrap();
+            assert_eq!(value, yaml(&schema));
+        }
+    }
+
+    #[test]
+    fn normalize_tuple_items_as_additional_items() {
+        let mut value =
+            yaml("{type: array, prefixItems: [{const: Sum}], items: false, maxItems: 1}");
+        normalize_tuple_items(&mut value, &pointer()).unwrap();
+        let keys: Vec<_> = (value.as_mapping().unwrap().keys())
+            .filter_map(serde_yaml::Value::as_str)
+            .collect();
+        assert_eq!(keys, ["type", "prefixItems", "additionalItems", "maxItems"]);
+        assert_eq!(value["additionalItems"], yaml("false"));
+
+        // The `items` of an array that isn't a tuple stay as they are.
+        let mut value = yaml("{type: array, items: {type: string}}");
+        normalize_tuple_items(&mut value, &pointer()).unwrap();
+        assert_eq!(value, yaml("{type: array, items: {type: string}}"));
+    }
+
+    #[test]
+    fn normalize_tuple_items_rejects_both_spellings() {
+        let mut value = yaml(
+            "{type: array, prefixItems: [{const: Sum}], items: false, additionalItems: false}",
+        );
+        let err = normalize_tuple_items(&mut value, &pointer()).unwrap_err();
+        assert_eq!(
+            err,
+            diag(
+                Code::InvalidSchema,
+                &pointer(),
+                "tuple has both `items` and `additionalItems`"
+            )
+        );
+    }
+
+    #[test]
+    fn explain_mismatch_of_closest_form() {
+        let explain = |schema: &str| explain_mismatch(&yaml(schema), &pointer());
+        assert_eq!(
+            explain("{type: array}"),
+            Some(diag(
+                Code::InvalidSchema,
+                &pointer(),
+                "schema matches no supported form; the closest is an array, which requires `items`"
+            ))
+        );
+        assert_eq!(
+            explain("{type: strng}"),
+            Some(diag(
+                Code::InvalidSchema,
+                &pointer().join("type"),
+                "unsupported type: \"strng\""
+            ))
+        );
+        assert_eq!(
+            explain("{type: string, minLength: -1}"),
+            Some(diag(
+                Code::InvalidSchema,
+                &pointer().join("minLength"),
+                "invalid value for `minLength` of a string"
+            ))
+        );
+        assert_eq!(explain("true"), None);
+    }
+
+    #[test]
+    fn explain_mismatch_blames_subschemas() {
+        let value =
+            yaml("{type: object, properties: {a: {anyOf: [{type: string}, {type: strng}]}}}");
+        let at = pointer().join("properties").join("a").join("anyOf").join(1);
+        assert_eq!(
+            explain_mismatch(&value, &pointer()),
+            Some(diag(
+                Code::InvalidSchema,
+                &at.join("type"),
+                "unsupported type: \"strng\""
+            ))
+        );
+    }
 }
@@ -0,0 +1,559 @@
+//! Resolving `$ref`s that point outside `#/components/schemas`, whether
+//! elsewhere in the spec or into other local files, by importing their targets
+//! into `#/components/schemas`. The backends then only ever see references to
+//! named schemas.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::codegen::{
+    SCHEMA_REF_PREFIX,
+    diagnostic::{Code, Diagnostic, Pointer, diag},
+    parse_document,
+};
+
+/// A document that references may point into: the spec itself, or another
+/// local file, identified by its canonical path.
+type Document = Option<PathBuf>;
+
+/// Imports the targets of the references in the schemas of `openapi` that
+/// point outside `#/components/schemas` into `#/components/schemas`, and
+/// rewrites the references to point there. `path` is the location of the
+/// spec, against which relative file references are resolved, if it's a
+/// local file.
+///
+/// An imported schema is named after the segments of its JSON pointer that
+/// name schemas, e.g. `#/components/schemas/Foo/properties/bar` as `FooBar`,
+/// or after its file if the reference is to a whole file. If that name is
+/// taken, the smallest numeric suffix that makes it unique is added. A schema
+/// in `#/components/schemas` that is just a reference, e.g. `Filter: {$ref:
+/// filters.yaml#/Filter}`, is replaced by the schema it references.
+///
+/// Returns the location that each imported or replaced schema came from.
+pub fn bundle(
+    openapi: &mut serde_yaml::Value,
+    path: Option<&Path>,
+) -> Result<BTreeMap<String, Pointer>, Diagnostic> {
+    let Some(schemas) = openapi["components"]["schemas"].as_mapping() else {
+        return Ok(BTreeMap::new());
+    };
+    let spec_path = path
+        .map(|path| {
+            path.canonicalize().map_err(|e| {
+                Diagnostic::new(Code::Other, format!("reading {}: {e}", path.display()))
+            })
+        })
+        .transpose()?;
+    let mut bundler = Bundler {
+        spec: openapi.clone(),
+        spec_path,
+        working_dir: env::current_dir().and_then(|dir| dir.canonicalize()).ok(),
+        documents: HashMap::new(),
+        names: HashMap::new(),
+        taken: schemas
+            .keys()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect(),
+        imported: vec![],
+        origins: BTreeMap::new(),
+    };
+
+    // Name the targets of schemas that are just references after them.
+    let mut aliases = BTreeSet::new();
+    for (name, schema) in schemas {
+        let (Some(name), Some(sref)) = (name.as_str(), schema_ref(schema)) else {
+            continue;
+        };
+        let pointer = Pointer::schema(name).join("$ref");
+        let target = bundler.locate(sref, &None, &pointer)?;
+        if !is_local_schema(&target) {
+            bundler.names.entry(target).or_insert_with(|| {
+                aliases.insert(name.to_string());
+                name.to_string()
+            });
+        }
+    }
+
+    let schemas = openapi["components"]["schemas"]
+        .as_mapping_mut()
+        .expect("checked above");
+    for (name, schema) in schemas.iter_mut() {
+        let name = name.as_str().unwrap_or_default();
+        let pointer = Pointer::schema(name);
+        match aliases.contains(name) {
+            true => {
+                let sref = schema_ref(schema).expect("checked above").to_string();
+                let target = bundler.locate(&sref, &None, &pointer.join("$ref"))?;
+                *schema = bundler.load(&target, &pointer.join("$ref"))?;
+                let origin = bundler.pointer(&target);
+                bundler.rewrite(schema, &target.0, &origin)?;
+                bundler.origins.insert(name.to_string(), origin);
+            }
+            false => bundler.rewrite(schema, &None, &pointer)?,
+        }
+    }
+    for (name, schema) in bundler.imported {
+        schemas.insert(name.into(), schema);
+    }
+    Ok(bundler.origins)
+}
+
+struct Bundler {
+    /// The spec, as it was before any references were rewritten.
+    spec: serde_yaml::Value,
+    spec_path: Option<PathBuf>,
+    /// The canonical working directory, against which the paths of other
+    /// documents are given in pointers.
+    working_dir: Option<PathBuf>,
+    /// The other documents loaded so far.
+    documents: HashMap<PathBuf, serde_yaml::Value>,
+    /// The names of the schemas that the targets of references, given by
+    /// document and JSON pointer, were imported as.
+    names: HashMap<(Document, String), String>,
+    /// The names of all schemas in `#/components/schemas`, including imported
+    /// ones.
+    taken: BTreeSet<String>,
+    imported: Vec<(String, serde_yaml::Value)>,
+    /// The location of each imported or replaced schema, by name.
+    origins: BTreeMap<String, Pointer>,
+}
+
+impl Bundler {
+    /// Rewrites the references in `value`, which is part of `document` at
+    /// `pointer`, to point into `#/components/schemas`, importing their
+    /// targets as needed.
+    fn rewrite(
+        &mut self,
+        value: &mut serde_yaml::Value,
+        document: &Document,
+        pointer: &Pointer,
+    ) -> Result<(), Diagnostic> {
+        match value {
+            serde_yaml::Value::Mapping(map) => {
+                for (key, child) in map.iter_mut() {
+                    let key = key.as_str().unwrap_or_default();
+                    let pointer = pointer.join(key);
+                    match (key, child) {
+                        ("$ref", serde_yaml::Value::String(sref)) => {
+                            *sref = self.import(sref, document, &pointer)?;
+                        }
+                        // Discriminator mappings name their variants by
+                        // reference too.
+                        ("discriminator", serde_yaml::Value::Mapping(discriminator)) => {
+                            let mapping = discriminator.get_mut("mapping");
+                            let mapping = mapping.and_then(serde_yaml::Value::as_mapping_mut);
+                            for (tag, target) in mapping.into_iter().flatten() {
+                                let tag = tag.as_str().unwrap_or_default();
+                                let pointer = pointer.join("mapping").join(tag);
+                                match target {
+                                    serde_yaml::Value::String(sref) if sref.contains('#') => {
+                                        *sref = self.import(sref, document, &pointer)?;
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        (_, child) => self.rewrite(child, document, &pointer)?,
+                    }
+                }
+            }
+            serde_yaml::Value::Sequence(values) => {
+                for (i, child) in values.iter_mut().enumerate() {
+                    self.rewrite(child, document, &pointer.join(i))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the reference in `#/components/schemas` to use in place of
+    /// `sref`, which appears in `document`, importing its target if it isn't
+    /// there already.
+    fn import(
+        &mut self,
+        sref: &str,
+        document: &Document,
+        pointer: &Pointer,
+    ) -> Result<String, Diagnostic> {
+        let target = self.locate(sref, document, pointer)?;
+        if is_local_schema(&target) {
+            return Ok(format!("#{}", target.1));
+        }
+        if let Some(name) = self.names.get(&target) {
+            return Ok(format!("{SCHEMA_REF_PREFIX}{name}"));
+        }
+
+        // Name the target before rewriting it, so that cycles end here.
+        let name = self.name(&target);
+        self.names.insert(target.clone(), name.clone());
+        let mut schema = self.load(&target, pointer)?;
+        let origin = self.pointer(&target);
+        self.rewrite(&mut schema, &target.0, &origin)?;
+        self.imported.push((name.clone(), schema));
+        self.origins.insert(name.clone(), origin);
+        Ok(format!("{SCHEMA_REF_PREFIX}{name}"))
+    }
+
+    /// Returns a name for the imported schema `target` that no other schema
+    /// has, and takes it.
+    fn name(&mut self, (document, fragment): &(Document, String)) -> String {
+        // Walk back from the target to the schema or component it's part of,
+        // or to the top level, skipping the keywords that lead to it.
+        let segments: Vec<_> = fragment.split('/').skip(1).map(unescape).collect();
+        let mut parts = vec![];
+        for (i, segment) in segments.iter().enumerate().rev() {
+            let parent = i.checked_sub(1).map(|i| segments[i].as_str());
+            let grandparent = i.checked_sub(2).map(|i| segments[i].as_str());
+            let is_keyword = parent != Some("properties")
+                && (SCHEMA_KEYWORDS.contains(&segment.as_str())
+                    || parent == Some("content")
+                    || segment.parse::<usize>().is_ok());
+            if is_keyword {
+                continue;
+            }
+            parts.push(segment.clone());
+            if i <= 1
+                || grandparent == Some("components")
+                || matches!(parent, Some("definitions" | "$defs"))
+            {
+                break;
+            }
+        }
+        if parts.is_empty() {
+            let stem = document.as_deref().and_then(Path::file_stem);
+            parts.push(stem.map_or("Schema".into(), |stem| stem.to_string_lossy().into_owned()));
+        }
+
+        // Keep a plain outermost name as it is, so that it still matches the
+        // managed prefixes, but spell the rest in upper camel case.
+        let mut name = String::new();
+        for (i, part) in parts.iter().rev().enumerate() {
+            match i == 0 && part.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                true => name.push_str(part),
+                false => {
+                    for word in part.split(|c: char| !c.is_alphanumeric()) {
+                        let mut chars = word.chars();
+                        name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                        name.extend(chars);
+                    }
+                }
+            }
+        }
+
+        let mut unique = name.clone();
+        let mut n = 1;
+        while !self.taken.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{name}{n}");
+        }
+        unique
+    }
+
+    /// Returns a pointer to `fragment` in `document`. Other documents are
+    /// given by their path relative to the working directory, if they're in
+    /// it.
+    fn pointer(&self, (document, fragment): &(Document, String)) -> Pointer {
+        let uri = match document {
+            None => String::new(),
+            Some(path) => {
+                let working_dir = self.working_dir.as_deref();
+                let relative = working_dir.and_then(|dir| path.strip_prefix(dir).ok());
+                relative.unwrap_or(path).display().to_string()
+            }
+        };
+        Pointer::new(&uri, fragment)
+    }
+
+    /// Returns the document and JSON pointer that `sref`, which appears in
+    /// `document`, refers to.
+    fn locate(
+        &self,
+        sref: &str,
+        document: &Document,
+        pointer: &Pointer,
+    ) -> Result<(Document, String), Diagnostic> {
+        let unsupported = |message: String| diag(Code::UnsupportedReference, pointer, message);
+        let (file, fragment) = sref.split_once('#').unwrap_or((sref, ""));
+        if !fragment.is_empty() && !fragment.starts_with('/') {
+            Err(unsupported(format!("unsupported reference: {sref}")))?
+        }
+        if file.is_empty() {
+            return Ok((document.clone(), fragment.to_string()));
+        }
+        if file.contains("://") {
+            Err(unsupported(format!(
+                "unsupported reference: {sref}; only local files can be referenced"
+            )))?
+        }
+        let base = match document {
+            Some(path) => path.as_path(),
+            None => self.spec_path.as_deref().ok_or_else(|| {
+                unsupported(format!(
+                    "unsupported reference: {sref}; files can only be referenced from a local spec"
+                ))
+            })?,
+        };
+        let path = base.parent().unwrap_or(Path::new("")).join(file);
+        let path = path.canonicalize().map_err(|e| {
+            diag(
+                Code::SchemaNotFound,
+                pointer,
+                format!("reading {}: {e}", path.display()),
+            )
+        })?;
+        let document = match Some(&path) == self.spec_path.as_ref() {
+            true => None,
+            false => Some(path),
+        };
+        Ok((document, fragment.to_string()))
+    }
+
+    /// Returns the value at `target`, loading its document if need be.
+    fn load(
+        &mut self,
+        target: &(Document, String),
+        pointer: &Pointer,
+    ) -> Result<serde_yaml::Value, Diagnostic> {
+        let not_found = format!("reference target not found: {}", self.pointer(target));
+        let (document, fragment) = target;
+        let value = match document {
+            None => &self.spec,
+            Some(path) => {
+                if !self.documents.contains_key(path) {
+                    let content = fs::read_to_string(path).map_err(|e| {
+                        diag(
+                            Code::SchemaNotFound,
+                            pointer,
+                            format!("reading {}: {e}", path.display()),
+                        )
+                    })?;
+                    let value = parse_document(&content).map_err(|d| {
+                        diag(
+                            d.code,
+                            pointer,
+                            format!("{}: {}", path.display(), d.message),
+                        )
+                    })?;
+                    self.documents.insert(path.clone(), value);
+                }
+                &self.documents[path]
+            }
+        };
+        let mut value = value;
+        for segment in fragment.split('/').skip(1) {
+            let segment = unescape(segment);
+            let child = match value {
+                serde_yaml::Value::Sequence(values) => {
+                    segment.parse().ok().and_then(|i: usize| values.get(i))
+                }
+                _ => value.get(segment.as_str()),
+            };
+            value = child.ok_or_else(|| diag(Code::SchemaNotFound, pointer, &not_found))?;
+        }
+        Ok(value.clone())
+    }
+}
+
+/// The keywords that lead from a schema, or from another part of a spec, to a
+/// schema within it, which imported schemas aren't named after.
+const SCHEMA_KEYWORDS: &[&str] = &[
+    "properties",
+    "items",
+    "additionalItems",
+    "additionalProperties",
+    "prefixItems",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+    "propertyNames",
+    "schema",
+    "content",
+    "parameters",
+    "requestBody",
+    "responses",
+    "headers",
+];
+
+/// Returns whether `target` is a schema in the spec's `#/components/schemas`.
+fn is_local_schema((document, fragment): &(Document, String)) -> bool {
+    document.is_none()
+        && fragment
+            .strip_prefix("/components/schemas/")
+            .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+}
+
+/// Returns the target of `schema` if it's just a reference.
+fn schema_ref(schema: &serde_yaml::Value) -> Option<&str> {
+    match schema.as_mapping()? {
+        map if map.len() == 1 => map.get("$ref")?.as_str(),
+        _ => None,
+    }
+}
+
+/// Unescapes a JSON pointer segment.
+fn unescape(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn yaml(s: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    /// Writes `files`, given by path and content, to a new temporary
+    /// directory, and returns it with its canonical path. The directory is
+    /// removed when the returned `TempDir` is dropped.
+    fn write_files(files: &[(&str, &str)]) -> (TempDir, PathBuf) {
+        let temp_dir = TempDir::with_prefix("apigen-").unwrap();
+        let dir = temp_dir.path().canonicalize().unwrap();
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        (temp_dir, dir)
+    }
+
+    /// Bundles the spec `openapi`, written to `dir` if given, returning its
+    /// schemas and the origins of the imported ones.
+    fn bundle_spec(
+        openapi: &str,
+        dir: Option<&Path>,
+    ) -> Result<(serde_yaml::Value, BTreeMap<String, Pointer>), Diagnostic> {
+        let path = dir.map(|dir| dir.join("openapi.yaml"));
+        if let Some(path) = &path {
+            fs::write(path, openapi).unwrap();
+        }
+        let mut openapi = yaml(openapi);
+        let origins = bundle(&mut openapi, path.as_deref())?;
+        Ok((openapi["components"]["schemas"].clone(), origins))
+    }
+
+    #[test]
+    fn imports_files_through_aliases_and_cycles() {
+        let (_temp_dir, dir) = write_files(&[(
+            "sub/filters.yaml",
+            "
+Filter:
+  anyOf:
+    - $ref: '#/FilterEq'
+    - $ref: '#/FilterAnd'
+FilterEq: {const: Eq}
+FilterAnd:
+  type: array
+  items: {$ref: '#/Filter'}
+",
+        )]);
+        let spec = "{components: {schemas: {Filter: {$ref: 'sub/filters.yaml#/Filter'}}}}";
+        let (schemas, origins) = bundle_spec(spec, Some(&dir)).unwrap();
+        assert_eq!(
+            schemas,
+            yaml(
+                "
+Filter:
+  anyOf:
+    - $ref: '#/components/schemas/FilterEq'
+    - $ref: '#/components/schemas/FilterAnd'
+FilterEq: {const: Eq}
+FilterAnd:
+  type: array
+  items: {$ref: '#/components/schemas/Filter'}
+"
+            )
+        );
+        let file = dir.join("sub/filters.yaml").display().to_string();
+        let origin = |fragment: &str| Pointer::new(&file, fragment);
+        assert_eq!(
+            origins,
+            BTreeMap::from([
+                ("Filter".into(), origin("/Filter")),
+                ("FilterAnd".into(), origin("/FilterAnd")),
+                ("FilterEq".into(), origin("/FilterEq")),
+            ])
+        );
+    }
+
+    #[test]
+    fn names_imports_after_the_schemas_they_are_part_of() {
+        let spec = "
+components:
+  schemas:
+    Nested:
+      type: object
+      properties:
+        inner: {type: object}
+    UsesNested:
+      type: array
+      items: {$ref: '#/components/schemas/Nested/properties/inner'}
+    Rank:
+      type: array
+      items: {$ref: '#/x-shared/Direction'}
+x-shared:
+  Direction: {const: asc}
+";
+        let (schemas, origins) = bundle_spec(spec, None).unwrap();
+        assert_eq!(
+            schemas["UsesNested"]["items"],
+            yaml("{$ref: '#/components/schemas/NestedInner'}")
+        );
+        assert_eq!(
+            schemas["Rank"]["items"],
+            yaml("{$ref: '#/components/schemas/Direction'}")
+        );
+        assert_eq!(
+            origins,
+            BTreeMap::from([
+                ("Direction".into(), Pointer::new("", "/x-shared/Direction")),
+                (
+                    "NestedInner".into(),
+                    Pointer::schema("Nested").join("properties").join("inner"),
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn disambiguates_colliding_names() {
+        let (_temp_dir, dir) = write_files(&[
+            ("a.yaml", "Filter: {const: A}"),
+            ("b.yaml", "Filter: {const: B}"),
+        ]);
+        let spec = "
+components:
+  schemas:
+    Filter: {type: string}
+    A: {type: array, items: {$ref: 'a.yaml#/Filter'}}
+    B: {type: array, items: {$ref: 'b.yaml#/Filter'}}
+";
+        let (schemas, _) = bundle_spec(spec, Some(&dir)).unwrap();
+        assert_eq!(schemas["Filter2"], yaml("{const: A}"));
+        assert_eq!(schemas["Filter3"], yaml("{const: B}"));
+        assert_eq!(
+            schemas["B"]["items"],
+            yaml("{$ref: '#/components/schemas/Filter3'}")
+        );
+    }
+
+    #[test]
+    fn reports_unreadable_files() {
+        let (_temp_dir, dir) = write_files(&[]);
+        let spec = "{components: {schemas: {A: {type: array, items: {$ref: 'a.yaml#/A'}}}}}";
+        let err = bundle(&mut yaml(spec), Some(&dir.join("openapi.yaml"))).unwrap_err();
+        assert_eq!(err.code, Code::Other);
+
+        let err = bundle_spec(spec, Some(&dir)).unwrap_err();
+        assert_eq!(err.code, Code::SchemaNotFound);
+        let pointer = Pointer::schema("A").join("items").join("$ref");
+        assert_eq!(err.pointer, Some(pointer));
+    }
+}
@@ -5,20 +5,34 @@ use std::{
 
 use crate::{
     codegen::{
-        OpenApiSchema, OpenApiSpec,
-        shared::{self, ConflictBehavior, TupleField},
+        ConstValue, Constraint, Discriminator, IntegerFormat, OpenApiSchema, OpenApiSpec,
+        TargetOptions,
+        diagnostic::{Code, Diagnostics, Pointer, diag},
+        format_bound,
+        shared::{self, ConflictBehavior, ObjectField, TupleField, object_fields},
         strip_schema_ref_prefix,
     },
     util::codegen_buf::CodegenBuf,
 };
 
-pub fn render(mut spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
-    shared::extract_any_of_tuples(&mut spec.managed_schemas, ConflictBehavior::AppendSuffix)?;
+const DEFAULT_NAMESPACE: &str = "Turbopuffer.Models.Namespaces";
+
+pub fn render(
+    mut spec: OpenApiSpec,
+    options: &TargetOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<CodegenBuf, Box<dyn Error>> {
+    shared::extract_any_of_tuples(&mut spec, ConflictBehavior::AppendSuffix)?;
     let ctx = RenderCtx {
-        inherits: shared::compute_inherits(&spec.managed_schemas)?,
+        inherits: shared::compute_inherits(&spec)?,
         objects_as_tuples: shared::rewrite_single_field_objects_to_tuples(
             &mut spec.managed_schemas,
         )?,
+        pointers: spec
+            .managed_schemas
+            .keys()
+            .map(|name| (name.clone(), spec.pointer(name)))
+            .collect(),
         schemas: spec.managed_schemas,
     };
 
@@ -26,18 +40,25 @@ pub fn render(mut spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
 
     buf.writeln("// Code generated by turbopuffer-apigen. DO NOT EDIT.");
     buf.writeln("");
+    // The generated code refers to its own obsolete declarations.
+    buf.writeln("#pragma warning disable CS0612, CS0618");
+    buf.writeln("");
     buf.writeln("using System;");
     buf.writeln("using System.Text.Json;");
     buf.writeln("using System.Text.Json.Serialization;");
     buf.writeln("");
-    buf.writeln("namespace Turbopuffer.Models.Namespaces;");
+    let namespace = options.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE);
+    buf.writeln(format!("namespace {namespace};"));
     buf.writeln("");
 
     for (i, (name, schema)) in ctx.schemas.iter().enumerate() {
-        if i > 0 {
-            buf.writeln("");
-        }
-        render_schema_top_level(&ctx, &mut buf, name, schema)?;
+        let pointer = &ctx.pointers[name];
+        diagnostics.record(&mut buf, pointer, |buf| {
+            if i > 0 {
+                buf.writeln("");
+            }
+            render_schema_top_level(&ctx, buf, name, schema, pointer)
+        });
     }
 
     Ok(buf)
@@ -50,6 +71,8 @@ struct RenderCtx {
     /// Names of objects that have been munged into tuples, and the JSON name
     /// overrides for their fields (`pascal -> original`).
     objects_as_tuples: BTreeMap<String, BTreeMap<String, String>>,
+    /// The location in the spec of each schema, for diagnostics.
+    pointers: BTreeMap<String, Pointer>,
     schemas: BTreeMap<String, OpenApiSchema>,
 }
 
@@ -69,24 +92,42 @@ fn render_schema_top_level(
     buf: &mut CodegenBuf,
     name: &str,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    render_doc(buf, schema.description(), schema.deprecated(), &[]);
     match schema {
+        OpenApiSchema::AnyOf { .. } if schema.without_null().is_some() => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "nullable schemas unsupported at top level",
+        ))?,
         OpenApiSchema::AnyOf { any_of, .. } => {
-            if any_of
-                .iter()
-                .all(|s| matches!(s, OpenApiSchema::Const { .. }))
-            {
+            if any_of.iter().all(|s| {
+                matches!(
+                    s,
+                    OpenApiSchema::Const {
+                        value: ConstValue::String(_),
+                        ..
+                    }
+                )
+            }) {
                 render_any_of_const_enum(buf, name, any_of)?;
             } else if any_of
                 .iter()
                 .all(|s| matches!(s, OpenApiSchema::Ref { .. }))
             {
-                render_any_of_refs(ctx, buf, name, any_of)?;
+                render_any_of_refs(ctx, buf, name, any_of, pointer, None)?;
             } else {
-                Err("unsupported anyOf")?
+                Err(diag(Code::UnsupportedAnyOf, pointer, "unsupported anyOf"))?
             }
         }
+        OpenApiSchema::OneOf {
+            one_of,
+            discriminator,
+            ..
+        } => render_any_of_refs(ctx, buf, name, one_of, pointer, Some(discriminator))?,
         OpenApiSchema::ArrayList { items, .. } => {
+            let items_pointer = pointer.join("items");
             render_wrapper_class(
                 ctx,
                 buf,
@@ -94,13 +135,13 @@ fn render_schema_top_level(
                 |buf| {
                     buf.start_line();
                     buf.write("public ");
-                    render_schema_inline(buf, items)?;
+                    render_schema_inline(buf, items, &items_pointer)?;
                     buf.write("[] Items { get; } = items;");
                     buf.end_line();
                     Ok(())
                 },
                 |buf| {
-                    render_schema_inline(buf, items)?;
+                    render_schema_inline(buf, items, &items_pointer)?;
                     buf.write("[] items");
                     Ok(())
                 },
@@ -141,6 +182,7 @@ fn render_schema_top_level(
             ..
         } => {
             let value_schema = &**additional_properties;
+            let value_pointer = pointer.join("additionalProperties");
             render_wrapper_class(
                 ctx,
                 buf,
@@ -149,14 +191,14 @@ fn render_schema_top_level(
                     buf.writeln("public string Name { get; } = name;");
                     buf.start_line();
                     buf.write("public ");
-                    render_schema_inline(buf, value_schema)?;
+                    render_schema_inline(buf, value_schema, &value_pointer)?;
                     buf.write(" Value { get; } = value;");
                     buf.end_line();
                     Ok(())
                 },
                 |buf| {
                     buf.write("string name, ");
-                    render_schema_inline(buf, value_schema)?;
+                    render_schema_inline(buf, value_schema, &value_pointer)?;
                     buf.write(" value");
                     Ok(())
                 },
@@ -170,14 +212,28 @@ fn render_schema_top_level(
             )?;
         }
         OpenApiSchema::ArrayTuple {
-            additional_items: true,
+            prefix_items,
+            additional_items,
             ..
-        } => Err("tuple-type arrays with `items: true` unsupported")?,
-        OpenApiSchema::ArrayTuple { prefix_items, .. } => {
-            render_array_tuple_class(ctx, buf, name, prefix_items)?;
+        } => {
+            let additional_items = additional_items.as_deref();
+            render_array_tuple_class(ctx, buf, name, prefix_items, additional_items, pointer)?;
         }
-        OpenApiSchema::Object { .. } => Err("object schemas in top-level unsupported")?,
-        _ => Err("schema type not supported at top level")?,
+        OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        } => render_object_class(ctx, buf, name, properties, required, pointer)?,
+        OpenApiSchema::Null { .. } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "nullable schemas unsupported at top level",
+        ))?,
+        _ => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "schema type not supported at top level",
+        ))?,
     }
     Ok(())
 }
@@ -248,6 +304,8 @@ fn render_array_tuple_class(
     buf: &mut CodegenBuf,
     name: &str,
     prefix_items: &[OpenApiSchema],
+    additional_items: Option<&OpenApiSchema>,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
     // Replace `object[]`-typed (i.e. `items: any`) list fields with generic
     // type parameters `T[]`, `U[]`, ... so callers can pass strongly-typed
@@ -264,10 +322,21 @@ fn render_array_tuple_class(
     let normal_fields: Vec<_> = fields
         .iter()
         .filter_map(|f| match f {
-            TupleField::Normal { name, schema } => Some((name.as_str(), *schema)),
+            TupleField::Normal {
+                name,
+                schema,
+                index,
+            } => Some((
+                name.as_str(),
+                *schema,
+                shared::tuple_field_pointer(pointer, json_overrides, *index),
+            )),
             TupleField::Const(_) => None,
         })
         .collect();
+    // A variadic tail becomes a trailing array property.
+    let tail = shared::tuple_tail(additional_items);
+    let tail_pointer = pointer.join("additionalItems");
 
     // Class declaration with primary constructor. Omit the `()` when there
     // are no parameters so the default ctor is implicit.
@@ -279,14 +348,30 @@ fn render_array_tuple_class(
     }
     buf.start_line();
     buf.write(format!("public sealed class {name}{generics}"));
-    if !normal_fields.is_empty() {
+    if !normal_fields.is_empty() || tail.is_some() {
+        let defaults = trailing_defaults(
+            normal_fields
+                .iter()
+                .map(|(_, schema, _)| render_default(schema))
+                .collect(),
+        );
         buf.write("(");
-        for (i, (prop_name, schema)) in normal_fields.iter().enumerate() {
+        for (i, (prop_name, schema, pointer)) in normal_fields.iter().enumerate() {
             if i > 0 {
                 buf.write(", ");
             }
-            render_schema_inline(buf, schema)?;
+            render_schema_inline(buf, schema, pointer)?;
             buf.write(format!(" {}", camel_case(prop_name)));
+            if let Some(default) = &defaults[i] {
+                buf.write(format!(" = {default}"));
+            }
+        }
+        if let Some((tail_name, schema)) = &tail {
+            if !normal_fields.is_empty() {
+                buf.write(", ");
+            }
+            render_schema_inline(buf, schema, &tail_pointer)?;
+            buf.write(format!("[] {}", camel_case(tail_name)));
         }
         buf.write(")");
     }
@@ -298,9 +383,10 @@ fn render_array_tuple_class(
     buf.indent();
 
     // Public properties bound to the primary constructor parameters.
-    for (prop_name, schema) in &normal_fields {
+    for (prop_name, schema, pointer) in &normal_fields {
         let prop_pascal = pascal_case(prop_name);
         let ctor_arg = camel_case(prop_name);
+        render_doc(buf, schema.description(), schema.deprecated(), &[]);
         buf.start_line();
         buf.write("public ");
         // If the property name collides with a factory method on a parent
@@ -308,11 +394,30 @@ fn render_array_tuple_class(
         if parent_factory_names.contains(&prop_pascal) {
             buf.write("new ");
         }
-        render_schema_inline(buf, schema)?;
-        buf.write(format!(" {prop_pascal} {{ get; }} = {ctor_arg};"));
+        render_schema_inline(buf, schema, pointer)?;
+        let nullable = schema.without_null().is_some();
+        buf.write(format!(
+            " {prop_pascal} {{ get; }} = {};",
+            render_checked(&ctor_arg, schema, nullable)
+        ));
         buf.end_line();
     }
-    if !normal_fields.is_empty() {
+    if let Some((tail_name, schema)) = &tail {
+        let prop_pascal = pascal_case(tail_name);
+        render_doc(buf, schema.description(), schema.deprecated(), &[]);
+        buf.start_line();
+        buf.write("public ");
+        if parent_factory_names.contains(&prop_pascal) {
+            buf.write("new ");
+        }
+        render_schema_inline(buf, schema, &tail_pointer)?;
+        buf.write(format!(
+            "[] {prop_pascal} {{ get; }} = {};",
+            render_checked_items(&camel_case(tail_name), schema)
+        ));
+        buf.end_line();
+    }
+    if !normal_fields.is_empty() || tail.is_some() {
         buf.writeln("");
     }
 
@@ -326,8 +431,8 @@ fn render_array_tuple_class(
         });
         for field in &fields {
             match field {
-                TupleField::Const(sconst) => {
-                    buf.writeln(format!("writer.WriteStringValue(\"{sconst}\");"));
+                TupleField::Const(value) => {
+                    buf.writeln(render_write_const(value));
                 }
                 TupleField::Normal {
                     name: prop_name, ..
@@ -349,6 +454,17 @@ fn render_array_tuple_class(
                 }
             }
         }
+        if let Some((tail_name, _)) = &tail {
+            buf.writeln(format!(
+                "foreach (var item in this.{})",
+                pascal_case(tail_name)
+            ));
+            buf.writeln("{");
+            buf.indent();
+            buf.writeln("JsonSerializer.Serialize(writer, item, options);");
+            buf.unindent();
+            buf.writeln("}");
+        }
         buf.writeln(if as_object {
             "writer.WriteEndObject();"
         } else {
@@ -361,6 +477,212 @@ fn render_array_tuple_class(
     Ok(())
 }
 
+/// Renders an object schema as a class with a property for each of the
+/// object's properties, except for `const` properties, which are implied by
+/// the class. Optional properties are nullable, default to null, and are
+/// omitted from the JSON when null.
+fn render_object_class(
+    ctx: &RenderCtx,
+    buf: &mut CodegenBuf,
+    name: &str,
+    properties: &BTreeMap<String, OpenApiSchema>,
+    required: &[String],
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let parent = ctx.inherits.get(name);
+    let parent_factory_names = parent
+        .map(|p| collect_factory_names(ctx, p))
+        .unwrap_or_default();
+    let fields = object_fields(properties, required, pointer);
+
+    // Class declaration with primary constructor, if there are any parameters.
+    // Variants pin the base's converter onto the concrete subtype (see the
+    // comment in `render_wrapper_class`); other objects have their own.
+    let converter = parent.map(String::as_str).unwrap_or(name);
+    buf.writeln(format!("[JsonConverter(typeof({converter}JsonConverter))]"));
+    buf.start_line();
+    buf.write(format!("public sealed class {name}"));
+    if !fields.is_empty() {
+        buf.write("(");
+        render_object_params(buf, &fields)?;
+        buf.write(")");
+    }
+    if let Some(parent) = parent {
+        buf.write(format!(" : {parent}"));
+    }
+    buf.end_line();
+    buf.writeln("{");
+    buf.indent();
+
+    // Public properties bound to the primary constructor parameters.
+    for field in &fields {
+        let prop_pascal = pascal_case(&field.name);
+        render_doc(
+            buf,
+            field.schema.description(),
+            field.schema.deprecated(),
+            &[],
+        );
+        buf.start_line();
+        buf.write("public ");
+        if parent_factory_names.contains(&prop_pascal) {
+            buf.write("new ");
+        }
+        render_object_field_type(buf, field)?;
+        let nullable = field.optional || field.schema.without_null().is_some();
+        buf.write(format!(
+            " {prop_pascal} {{ get; }} = {};",
+            render_checked(&camel_case(&field.name), field.schema, nullable)
+        ));
+        buf.end_line();
+    }
+    if !fields.is_empty() {
+        buf.writeln("");
+    }
+
+    // WriteJson, which overrides the base's if there is one.
+    let write_body = |buf: &mut CodegenBuf| {
+        buf.writeln("writer.WriteStartObject();");
+        let mut fields = fields.iter();
+        for (json_name, schema) in properties {
+            let json_name = escape_csharp_string(json_name);
+            let OpenApiSchema::Const { value, .. } = schema else {
+                continue;
+            };
+            buf.writeln(format!("writer.WritePropertyName({json_name});"));
+            buf.writeln(render_write_const(value));
+        }
+        for field in fields.by_ref() {
+            let json_name = escape_csharp_string(field.json_name);
+            let prop_pascal = pascal_case(&field.name);
+            if field.optional {
+                buf.writeln(format!("if (this.{prop_pascal} != null)"));
+                buf.writeln("{");
+                buf.indent();
+            }
+            buf.writeln(format!("writer.WritePropertyName({json_name});"));
+            buf.writeln(format!(
+                "JsonSerializer.Serialize(writer, this.{prop_pascal}, options);"
+            ));
+            if field.optional {
+                buf.unindent();
+                buf.writeln("}");
+            }
+        }
+        buf.writeln("writer.WriteEndObject();");
+        Ok(())
+    };
+    if parent.is_some() {
+        render_write_json_override(buf, write_body)?;
+    } else {
+        buf.writeln(
+            "internal void WriteJson(Utf8JsonWriter writer, JsonSerializerOptions options)",
+        );
+        buf.writeln("{");
+        buf.indent();
+        write_body(buf)?;
+        buf.unindent();
+        buf.writeln("}");
+    }
+
+    // Deserialization, for the converter to dispatch to.
+    buf.writeln("");
+    buf.writeln(format!(
+        "internal static {name} FromJson(JsonElement value, JsonSerializerOptions options) =>"
+    ));
+    buf.indent();
+    buf.start_line();
+    buf.write(format!("new {name}("));
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.write(", ");
+        }
+        let json_name = escape_csharp_string(field.json_name);
+        if field.optional {
+            buf.write(format!(
+                "value.TryGetProperty({json_name}, out var p{i}) ? JsonSerializer.Deserialize<"
+            ));
+            render_object_field_type(buf, field)?;
+            buf.write(format!(">(p{i}, options) : null"));
+        } else {
+            buf.write("JsonSerializer.Deserialize<");
+            render_object_field_type(buf, field)?;
+            buf.write(format!(">(value.GetProperty({json_name}), options)!"));
+        }
+    }
+    buf.write(");");
+    buf.end_line();
+    buf.unindent();
+
+    buf.unindent();
+    buf.writeln("}");
+
+    if parent.is_none() {
+        buf.writeln("");
+        buf.writeln(format!(
+            "internal sealed class {name}JsonConverter : JsonConverter<{name}>"
+        ));
+        buf.writeln("{");
+        buf.indent();
+        buf.writeln(format!(
+            "public override {name} Read(ref Utf8JsonReader reader, Type typeToConvert, JsonSerializerOptions options) =>"
+        ));
+        buf.indent();
+        buf.writeln(format!(
+            "{name}.FromJson(JsonElement.ParseValue(ref reader), options);"
+        ));
+        buf.unindent();
+        buf.writeln("");
+        buf.writeln(format!(
+            "public override void Write(Utf8JsonWriter writer, {name} value, JsonSerializerOptions options) =>"
+        ));
+        buf.indent();
+        buf.writeln("value.WriteJson(writer, options);");
+        buf.unindent();
+        buf.unindent();
+        buf.writeln("}");
+    }
+    Ok(())
+}
+
+fn render_object_field_type(
+    buf: &mut CodegenBuf,
+    field: &ObjectField,
+) -> Result<(), Box<dyn Error>> {
+    render_schema_inline(buf, field.schema, &field.pointer)?;
+    if field.optional && field.schema.without_null().is_none() {
+        buf.write("?");
+    }
+    Ok(())
+}
+
+/// Renders `fields` as constructor parameters, with their schemas' defaults,
+/// if any, or else a default of null for the optional ones.
+fn render_object_params(
+    buf: &mut CodegenBuf,
+    fields: &[ObjectField],
+) -> Result<(), Box<dyn Error>> {
+    let defaults = trailing_defaults(
+        fields
+            .iter()
+            .map(|field| {
+                render_default(field.schema).or_else(|| field.optional.then(|| "null".into()))
+            })
+            .collect(),
+    );
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.write(", ");
+        }
+        render_object_field_type(buf, field)?;
+        buf.write(format!(" {}", camel_case(&field.name)));
+        if let Some(default) = &defaults[i] {
+            buf.write(format!(" = {default}"));
+        }
+    }
+    Ok(())
+}
+
 fn render_any_of_const_enum(
     buf: &mut CodegenBuf,
     name: &str,
@@ -369,13 +691,18 @@ fn render_any_of_const_enum(
     let entries: Vec<_> = schema
         .iter()
         .map(|item| {
-            let OpenApiSchema::Const { title, sconst, .. } = item else {
+            let OpenApiSchema::Const {
+                title,
+                value: ConstValue::String(sconst),
+                ..
+            } = item
+            else {
                 unreachable!("validated by caller");
             };
             let member = title
                 .clone()
                 .unwrap_or_else(|| sconst_to_screaming_snake(sconst));
-            (member, sconst.clone())
+            (member, sconst.clone(), item)
         })
         .collect();
 
@@ -383,7 +710,8 @@ fn render_any_of_const_enum(
     buf.writeln(format!("public enum {name}"));
     buf.writeln("{");
     buf.indent();
-    for (member, _) in &entries {
+    for (member, _, item) in &entries {
+        render_doc(buf, item.description(), item.deprecated(), &[]);
         buf.writeln(format!("{member},"));
     }
     buf.unindent();
@@ -404,7 +732,7 @@ fn render_any_of_const_enum(
     buf.writeln("reader.GetString() switch");
     buf.writeln("{");
     buf.indent();
-    for (member, sconst) in &entries {
+    for (member, sconst, _) in &entries {
         buf.writeln(format!("\"{sconst}\" => {name}.{member},"));
     }
     buf.writeln(format!(
@@ -423,7 +751,7 @@ fn render_any_of_const_enum(
     buf.writeln("writer.WriteStringValue(value switch");
     buf.writeln("{");
     buf.indent();
-    for (member, sconst) in &entries {
+    for (member, sconst, _) in &entries {
         buf.writeln(format!("{name}.{member} => \"{sconst}\","));
     }
     buf.writeln(format!(
@@ -442,8 +770,14 @@ fn render_any_of_refs(
     buf: &mut CodegenBuf,
     name: &str,
     schema: &[OpenApiSchema],
+    pointer: &Pointer,
+    discriminator: Option<&Discriminator>,
 ) -> Result<(), Box<dyn Error>> {
     let parent = ctx.inherits.get(name);
+    let key = match discriminator {
+        Some(_) => "oneOf",
+        None => "anyOf",
+    };
 
     // Abstract base.
     buf.writeln(format!("[JsonConverter(typeof({name}JsonConverter))]"));
@@ -465,14 +799,14 @@ fn render_any_of_refs(
     }
 
     // Factory methods.
-    for item in schema {
-        let OpenApiSchema::Ref { sref, title } = item else {
+    for (i, item) in schema.iter().enumerate() {
+        let OpenApiSchema::Ref { sref, title, .. } = item else {
             unreachable!("validated by caller");
         };
-        let sref = strip_schema_ref_prefix(sref)?;
+        let sref = strip_schema_ref_prefix(sref, &pointer.join(key).join(i))?;
         let subname = title.as_deref().unwrap_or(sref);
         let factory_name = pascal_case(subname.strip_prefix(name).unwrap_or(subname));
-        render_factory(ctx, buf, sref, &factory_name)?;
+        render_factory(ctx, buf, item, sref, &factory_name)?;
     }
 
     buf.unindent();
@@ -512,14 +846,48 @@ fn render_any_of_refs(
     buf.writeln(format!("typeof({name}).IsAssignableFrom(typeToConvert);"));
     buf.unindent();
     buf.writeln("");
-    buf.writeln(format!(
-        "public override {name} Read(ref Utf8JsonReader reader, Type typeToConvert, JsonSerializerOptions options) =>"
-    ));
-    buf.indent();
-    buf.writeln(format!(
-        "new {name}Raw(JsonElement.ParseValue(ref reader));"
-    ));
-    buf.unindent();
+    if let Some(discriminator) = discriminator {
+        // Dispatch on the discriminator, falling back to the raw variant for
+        // unknown tags.
+        let variants = shared::tagged_variants(&ctx.schemas, schema, discriminator, pointer)?;
+        buf.writeln(format!(
+            "public override {name} Read(ref Utf8JsonReader reader, Type typeToConvert, JsonSerializerOptions options)"
+        ));
+        buf.writeln("{");
+        buf.indent();
+        buf.writeln("var value = JsonElement.ParseValue(ref reader);");
+        buf.writeln(format!(
+            "if (value.ValueKind == JsonValueKind.Object && value.TryGetProperty({}, out var tag) && tag.ValueKind == JsonValueKind.String)",
+            escape_csharp_string(&discriminator.property_name)
+        ));
+        buf.writeln("{");
+        buf.indent();
+        buf.writeln("switch (tag.GetString())");
+        buf.writeln("{");
+        buf.indent();
+        for variant in &variants {
+            buf.writeln(format!("case {}:", escape_csharp_string(variant.tag)));
+            buf.indent();
+            buf.writeln(format!("return {}.FromJson(value, options);", variant.name));
+            buf.unindent();
+        }
+        buf.unindent();
+        buf.writeln("}");
+        buf.unindent();
+        buf.writeln("}");
+        buf.writeln(format!("return new {name}Raw(value);"));
+        buf.unindent();
+        buf.writeln("}");
+    } else {
+        buf.writeln(format!(
+            "public override {name} Read(ref Utf8JsonReader reader, Type typeToConvert, JsonSerializerOptions options) =>"
+        ));
+        buf.indent();
+        buf.writeln(format!(
+            "new {name}Raw(JsonElement.ParseValue(ref reader));"
+        ));
+        buf.unindent();
+    }
     buf.writeln("");
     buf.writeln(format!(
         "public override void Write(Utf8JsonWriter writer, {name} value, JsonSerializerOptions options) =>"
@@ -536,21 +904,48 @@ fn render_any_of_refs(
 fn render_factory(
     ctx: &RenderCtx,
     buf: &mut CodegenBuf,
+    arm: &OpenApiSchema,
     sref: &str,
     factory_name: &str,
 ) -> Result<(), Box<dyn Error>> {
+    let pointer = &ctx.pointers[sref];
+    let description = ctx.schemas[sref].description();
+    let deprecated = arm.deprecated().or(ctx.schemas[sref].deprecated());
     match &ctx.schemas[sref] {
-        OpenApiSchema::ArrayTuple { prefix_items, .. } => {
+        OpenApiSchema::ArrayTuple {
+            prefix_items,
+            additional_items,
+            ..
+        } => {
             let mut prefix_items = prefix_items.clone();
             let generics = format_generics(&shared::assign_generics(&mut prefix_items));
             let fields = shared::build_tuple_fields(&prefix_items);
+            let tail = shared::tuple_tail(additional_items.as_deref());
+            let json_overrides = ctx.objects_as_tuples.get(sref);
             let normal_fields: Vec<_> = fields
                 .iter()
                 .filter_map(|f| match f {
-                    TupleField::Normal { name, schema } => Some((name.as_str(), *schema)),
+                    TupleField::Normal {
+                        name,
+                        schema,
+                        index,
+                    } => Some((
+                        name.as_str(),
+                        *schema,
+                        shared::tuple_field_pointer(pointer, json_overrides, *index),
+                    )),
                     _ => None,
                 })
                 .collect();
+            let params: Vec<_> = normal_fields
+                .iter()
+                .map(|(prop_name, schema, _)| (camel_case(prop_name), schema.description()))
+                .chain(
+                    tail.iter()
+                        .map(|(name, schema)| (camel_case(name), schema.description())),
+                )
+                .collect();
+            render_doc(buf, description, deprecated, &params);
             buf.start_line();
             buf.write(format!(
                 "public static {sref}{generics} {factory_name}{generics}("
@@ -561,15 +956,22 @@ fn render_factory(
             // `Filter.Or`). Factories like `Filter.In(attr, value)` or
             // `RankBy.Ann(attr, vector)` have a second normal field, so the
             // list there is a single payload argument, not a variadic one.
-            let use_params = normal_fields.len() == 1;
-            for (i, (prop_name, schema)) in normal_fields.iter().enumerate() {
+            // A variadic tail takes the `params` slot when there is one.
+            let use_params = normal_fields.len() == 1 && tail.is_none();
+            let defaults = trailing_defaults(
+                normal_fields
+                    .iter()
+                    .map(|(_, schema, _)| render_default(schema))
+                    .collect(),
+            );
+            for (i, (prop_name, schema, pointer)) in normal_fields.iter().enumerate() {
                 if i > 0 {
                     buf.write(", ");
                 }
                 match (use_params, schema) {
                     (true, OpenApiSchema::ArrayList { .. }) => {
                         buf.write("params ");
-                        render_schema_inline(buf, schema)?;
+                        render_schema_inline(buf, schema, pointer)?;
                     }
                     // For non-variadic list payloads (e.g. `RankBy.Ann(attr,
                     // value)`, `Filter.In(attr, value)`), accept any
@@ -587,16 +989,27 @@ fn render_factory(
                         if let Some(generic) = shared::array_list_generic(description) {
                             buf.write(generic);
                         } else {
-                            render_schema_inline(buf, items)?;
+                            render_schema_inline(buf, items, &pointer.join("items"))?;
                         }
                         buf.write(">");
                     }
-                    _ => render_schema_inline(buf, schema)?,
+                    _ => render_schema_inline(buf, schema, pointer)?,
                 }
                 buf.write(format!(" {}", camel_case(prop_name)));
+                if let Some(default) = &defaults[i] {
+                    buf.write(format!(" = {default}"));
+                }
+            }
+            if let Some((tail_name, schema)) = &tail {
+                if !normal_fields.is_empty() {
+                    buf.write(", ");
+                }
+                buf.write("params ");
+                render_schema_inline(buf, schema, &pointer.join("additionalItems"))?;
+                buf.write(format!("[] {}", camel_case(tail_name)));
             }
             buf.write(format!(") => new {sref}{generics}("));
-            for (i, (prop_name, schema)) in normal_fields.iter().enumerate() {
+            for (i, (prop_name, schema, _)) in normal_fields.iter().enumerate() {
                 if i > 0 {
                     buf.write(", ");
                 }
@@ -607,17 +1020,25 @@ fn render_factory(
                     buf.write(arg);
                 }
             }
+            if let Some((tail_name, _)) = &tail {
+                if !normal_fields.is_empty() {
+                    buf.write(", ");
+                }
+                buf.write(camel_case(tail_name));
+            }
             buf.write(");");
             buf.end_line();
         }
         OpenApiSchema::ArrayList { items, .. } => {
+            render_doc(buf, description, deprecated, &[]);
             buf.start_line();
             buf.write(format!("public static {sref} {factory_name}(params "));
-            render_schema_inline(buf, items)?;
+            render_schema_inline(buf, items, &pointer.join("items"))?;
             buf.write(format!("[] items) => new {sref}(items);"));
             buf.end_line();
         }
         OpenApiSchema::String { .. } => {
+            render_doc(buf, description, deprecated, &[]);
             buf.writeln(format!(
                 "public static {sref} {factory_name}(string value) => new {sref}(value);"
             ));
@@ -626,12 +1047,38 @@ fn render_factory(
             additional_properties,
             ..
         } => {
+            render_doc(buf, description, deprecated, &[]);
             buf.start_line();
             buf.write(format!("public static {sref} {factory_name}(string name, "));
-            render_schema_inline(buf, additional_properties)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema_inline(buf, additional_properties, &pointer)?;
             buf.write(format!(" value) => new {sref}(name, value);"));
             buf.end_line();
         }
+        OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        } => {
+            let fields = object_fields(properties, required, pointer);
+            let params: Vec<_> = fields
+                .iter()
+                .map(|field| (camel_case(&field.name), field.schema.description()))
+                .collect();
+            render_doc(buf, description, deprecated, &params);
+            buf.start_line();
+            buf.write(format!("public static {sref} {factory_name}("));
+            render_object_params(buf, &fields)?;
+            buf.write(format!(") => new {sref}("));
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 {
+                    buf.write(", ");
+                }
+                buf.write(camel_case(&field.name));
+            }
+            buf.write(");");
+            buf.end_line();
+        }
         // No factory for variants whose target is itself an `anyOf`: those
         // become abstract sub-types and are instantiated through their own
         // factories.
@@ -640,19 +1087,66 @@ fn render_factory(
     Ok(())
 }
 
+/// Writes `description` and the descriptions of the named `params`, if any,
+/// as an XML doc comment, followed by the `deprecated` message, if any, as an
+/// `[Obsolete]` attribute.
+fn render_doc(
+    buf: &mut CodegenBuf,
+    description: Option<&str>,
+    deprecated: Option<&str>,
+    params: &[(String, Option<&str>)],
+) {
+    let escape = |s: &str| {
+        s.trim()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    if let Some(description) = description {
+        buf.writeln("/// <summary>");
+        shared::write_doc(buf, "/// ", &escape(description));
+        buf.writeln("/// </summary>");
+    }
+    for (name, description) in params {
+        if let Some(description) = description {
+            let description = escape(description);
+            shared::write_doc(
+                buf,
+                "/// ",
+                &format!("<param name=\"{name}\">{description}</param>"),
+            );
+        }
+    }
+    if let Some(message) = deprecated {
+        let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+        buf.writeln(format!("[Obsolete({})]", escape_csharp_string(&message)));
+    }
+}
+
 fn render_schema_inline(
     buf: &mut CodegenBuf,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    if let Some(schema) = schema.without_null() {
+        render_schema_inline(buf, &schema, pointer)?;
+        buf.write("?");
+        return Ok(());
+    }
     match schema {
-        OpenApiSchema::AnyOf { .. } => buf.write("object"),
-        OpenApiSchema::Object { .. } => Err("inline object schemas unsupported")?,
+        OpenApiSchema::AnyOf { .. } | OpenApiSchema::OneOf { .. } => buf.write("object"),
+        OpenApiSchema::Object { .. } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "inline object schemas unsupported",
+        ))?,
         OpenApiSchema::Map {
             additional_properties,
             ..
         } => {
             buf.write("System.Collections.Generic.IReadOnlyDictionary<string, ");
-            render_schema_inline(buf, additional_properties)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema_inline(buf, additional_properties, &pointer)?;
             buf.write(">");
         }
         OpenApiSchema::ArrayList {
@@ -661,11 +1155,15 @@ fn render_schema_inline(
             if let Some(generic) = shared::array_list_generic(description) {
                 buf.write(generic);
             } else {
-                render_schema_inline(buf, items)?;
+                render_schema_inline(buf, items, &pointer.join("items"))?;
             }
             buf.write("[]");
         }
-        OpenApiSchema::ArrayTuple { .. } => Err("inline array tuple unsupported")?,
+        OpenApiSchema::ArrayTuple { .. } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "inline array tuple unsupported",
+        ))?,
         OpenApiSchema::String { .. } => buf.write("string"),
         OpenApiSchema::Boolean { .. } => buf.write("bool"),
         OpenApiSchema::Number {
@@ -674,10 +1172,26 @@ fn render_schema_inline(
         } => match x_turbopuffer_width {
             Some(32) => buf.write("float"),
             None | Some(64) => buf.write("double"),
-            Some(w) => Err(format!("unsupported number width: {w}"))?,
+            Some(w) => Err(diag(
+                Code::UnsupportedNumberWidth,
+                pointer,
+                format!("unsupported number width: {w}"),
+            ))?,
         },
-        OpenApiSchema::Const { .. } => Err("inline const in unsupported position")?,
-        OpenApiSchema::Ref { sref, .. } => buf.write(strip_schema_ref_prefix(sref)?),
+        OpenApiSchema::Integer { format, .. } => {
+            match IntegerFormat::parse(format.as_deref(), pointer)? {
+                IntegerFormat::Int32 => buf.write("int"),
+                IntegerFormat::Unspecified | IntegerFormat::Int64 => buf.write("long"),
+                IntegerFormat::Uint64 => buf.write("ulong"),
+            }
+        }
+        OpenApiSchema::Const { .. } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "inline const in unsupported position",
+        ))?,
+        OpenApiSchema::Ref { sref, .. } => buf.write(strip_schema_ref_prefix(sref, pointer)?),
+        OpenApiSchema::Null { .. } => buf.write("object?"),
         OpenApiSchema::Any { .. } => buf.write("object"),
     }
     Ok(())
@@ -690,12 +1204,18 @@ fn collect_factory_names(ctx: &RenderCtx, name: &str) -> BTreeSet<String> {
     let mut out = BTreeSet::new();
     let mut current = Some(name);
     while let Some(cur) = current {
-        if let Some(OpenApiSchema::AnyOf { any_of, .. }) = ctx.schemas.get(cur) {
-            for item in any_of {
-                let OpenApiSchema::Ref { sref, title } = item else {
+        let arms = match ctx.schemas.get(cur) {
+            Some(OpenApiSchema::AnyOf { any_of, .. }) => Some(("anyOf", any_of)),
+            Some(OpenApiSchema::OneOf { one_of, .. }) => Some(("oneOf", one_of)),
+            _ => None,
+        };
+        if let Some((key, arms)) = arms {
+            for (i, item) in arms.iter().enumerate() {
+                let OpenApiSchema::Ref { sref, title, .. } = item else {
                     continue;
                 };
-                let Ok(sref) = strip_schema_ref_prefix(sref) else {
+                let pointer = ctx.pointers[cur].join(key).join(i);
+                let Ok(sref) = strip_schema_ref_prefix(sref, &pointer) else {
                     continue;
                 };
                 // No factory is emitted for variants whose target is itself
@@ -758,8 +1278,132 @@ fn sconst_to_screaming_snake(sconst: &str) -> String {
     s
 }
 
+/// Returns the initializer of the property bound to the constructor parameter
+/// `param`, which throws if the parameter violates the constraints of its
+/// `schema`. Primary constructors have no body to check their parameters in.
+fn render_checked(param: &str, schema: &OpenApiSchema, nullable: bool) -> String {
+    let mut initializer = String::new();
+    for constraint in schema.constraints() {
+        let violated = render_violated(param, &constraint);
+        let exception = render_exception(param, &constraint, &constraint.message(param));
+        if nullable {
+            initializer.push_str(&format!("{param} != null && "));
+        }
+        initializer.push_str(&format!("{violated} ? throw new {exception} : "));
+    }
+    initializer.push_str(param);
+    initializer
+}
+
+/// Like `render_checked`, but for the array parameter `param` of a variadic
+/// tuple tail, each of whose items must satisfy the constraints of `schema`.
+fn render_checked_items(param: &str, schema: &OpenApiSchema) -> String {
+    let nullable = schema.without_null().is_some();
+    let mut initializer = String::new();
+    for constraint in schema.constraints() {
+        let mut violated = render_violated("item", &constraint);
+        if nullable {
+            violated = format!("item != null && {violated}");
+        }
+        let message = constraint.message(&format!("{param}[i]"));
+        let exception = render_exception(param, &constraint, &message);
+        initializer.push_str(&format!(
+            "System.Array.Exists({param}, item => {violated}) ? throw new {exception} : "
+        ));
+    }
+    initializer.push_str(param);
+    initializer
+}
+
+/// Returns the condition under which `value` violates `constraint`.
+fn render_violated(value: &str, constraint: &Constraint) -> String {
+    let runes = || format!("System.Linq.Enumerable.Count({value}.EnumerateRunes())");
+    match constraint {
+        Constraint::MinItems(n) => format!("{value}.Length < {n}"),
+        Constraint::MaxItems(n) => format!("{value}.Length > {n}"),
+        Constraint::MinLength(n) => format!("{} < {n}", runes()),
+        Constraint::MaxLength(n) => format!("{} > {n}", runes()),
+        Constraint::Minimum(n) => format!("{value} < {}", format_bound(*n)),
+        Constraint::ExclusiveMinimum(n) => format!("{value} <= {}", format_bound(*n)),
+        Constraint::Maximum(n) => format!("{value} > {}", format_bound(*n)),
+        Constraint::ExclusiveMaximum(n) => format!("{value} >= {}", format_bound(*n)),
+        Constraint::Pattern(pattern) => format!(
+            "!System.Text.RegularExpressions.Regex.IsMatch({value}, {})",
+            escape_csharp_string(pattern)
+        ),
+    }
+}
+
+/// Returns the exception thrown when the parameter `param` violates
+/// `constraint`, with `message`.
+fn render_exception(param: &str, constraint: &Constraint, message: &str) -> String {
+    let message = escape_csharp_string(message);
+    match constraint {
+        Constraint::Minimum(_)
+        | Constraint::ExclusiveMinimum(_)
+        | Constraint::Maximum(_)
+        | Constraint::ExclusiveMaximum(_) => {
+            format!("ArgumentOutOfRangeException(nameof({param}), {message})")
+        }
+        _ => format!("ArgumentException({message}, nameof({param}))"),
+    }
+}
+
+/// Returns the C# literal of the `default` of `schema`, if it has one.
+fn render_default(schema: &OpenApiSchema) -> Option<String> {
+    let value = schema.default()?;
+    let schema = schema.without_null().unwrap_or_else(|| schema.clone());
+    Some(match (schema, value) {
+        (
+            OpenApiSchema::Number {
+                x_turbopuffer_width,
+                ..
+            },
+            _,
+        ) => {
+            let n = value.as_f64().expect("validated when parsing");
+            match x_turbopuffer_width {
+                Some(32) => format!("{n:?}f"),
+                _ => format!("{n:?}"),
+            }
+        }
+        (_, ConstValue::String(s)) => escape_csharp_string(s),
+        (_, ConstValue::UInteger(u)) => format!("{u}UL"),
+        // Integers and booleans are spelled the same in JSON and C#.
+        _ => value.to_string(),
+    })
+}
+
+/// Keeps only the trailing run of `defaults`, since C# requires optional
+/// parameters to come after all required ones.
+fn trailing_defaults(mut defaults: Vec<Option<String>>) -> Vec<Option<String>> {
+    if let Some(last_required) = defaults.iter().rposition(Option::is_none) {
+        defaults[..last_required].fill(None);
+    }
+    defaults
+}
+
+/// Returns the statement that writes a const value to `writer`.
+fn render_write_const(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Null => "writer.WriteNullValue();".to_string(),
+        ConstValue::Bool(b) => format!("writer.WriteBooleanValue({b});"),
+        ConstValue::Integer(_) | ConstValue::Number(_) => {
+            format!("writer.WriteNumberValue({value});")
+        }
+        ConstValue::UInteger(u) => format!("writer.WriteNumberValue({u}UL);"),
+        ConstValue::String(s) => format!("writer.WriteStringValue({});", escape_csharp_string(s)),
+    }
+}
+
 fn escape_csharp_string(s: &str) -> String {
-    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
 }
 
 fn is_csharp_keyword(s: &str) -> bool {
@@ -844,3 +1488,47 @@ fn is_csharp_keyword(s: &str) -> bool {
             | "while"
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::{CONSTS, UINT64, render_schemas};
+
+    #[test]
+    fn consts() {
+        let out = render_schemas(render, CONSTS).unwrap();
+        assert!(
+            out.contains("writer.WriteNumberValue(18446744073709551615UL);"),
+            "{out}"
+        );
+        assert!(out.contains("writer.WriteNumberValue(-3);"), "{out}");
+        assert!(
+            out.contains(r#"writer.WriteStringValue("a\nb\t$c\"\r");"#),
+            "{out}"
+        );
+    }
+
+    const DEFAULTS: &str = "
+RankByOpts:
+  type: object
+  properties:
+    query: {type: string}
+    big: {type: integer, format: uint64, default: 18446744073709551615}
+  required: [query]
+";
+
+    #[test]
+    fn uint64_defaults() {
+        let out = render_schemas(render, DEFAULTS).unwrap();
+        assert!(out.contains("ulong? big = 18446744073709551615UL"), "{out}");
+    }
+
+    #[test]
+    fn uint64_bounds() {
+        let out = render_schemas(render, UINT64).unwrap();
+        assert!(out.contains("ulong n"), "{out}");
+        assert!(out.contains("n < 1 ? throw"), "{out}");
+        assert!(out.contains("n > 100 ? throw"), "{out}");
+        assert!(!out.contains("n < 0"), "{out}");
+    }
+}
@@ -0,0 +1,811 @@
+//! Diagnostics for problems in the OpenAPI spec.
+
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use crate::Language;
+use crate::util::codegen_buf::CodegenBuf;
+
+/// The format in which to report diagnostics.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiagnosticsFormat {
+    /// One human-readable line per diagnostic.
+    #[default]
+    Text,
+    /// A JSON array with one object per diagnostic.
+    Json,
+    /// A SARIF 2.1.0 log, for annotating the spec in code review tools.
+    Sarif,
+}
+
+/// A stable identifier for a class of diagnostic.
+///
+/// The string form of each code (see [`Code::as_str`]) is part of the
+/// generator's interface: CI tooling may match on it, so it must never change
+/// once released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Code {
+    /// The spec is not a well-formed OpenAPI document.
+    InvalidSpec,
+    /// A managed schema doesn't match any supported schema shape.
+    InvalidSchema,
+    /// A `$ref` has an unsupported form.
+    UnsupportedReference,
+    /// A `$ref` names a schema that doesn't exist.
+    SchemaNotFound,
+    /// Two schemas would be generated with the same name.
+    DuplicateSchema,
+    /// An `anyOf` whose variants can't be represented in the target language.
+    UnsupportedAnyOf,
+    /// A `oneOf` whose `discriminator` is malformed or inconsistent with its
+    /// variants.
+    InvalidDiscriminator,
+    /// An object schema whose shape can't be represented in the target
+    /// language.
+    UnsupportedObject,
+    /// A tuple schema with a shape that can't be represented in the target
+    /// language.
+    UnsupportedTuple,
+    /// A number schema with an unsupported `x-turbopuffer-width`.
+    UnsupportedNumberWidth,
+    /// An integer schema with an unsupported `format`.
+    UnsupportedIntegerFormat,
+    /// A string schema whose `pattern` the target language's regular
+    /// expressions can't express.
+    UnsupportedPattern,
+    /// A schema that is supported in general, but not where it appears.
+    UnsupportedPosition,
+    /// An error that is not attributable to the spec, like a network error.
+    Other,
+}
+
+impl Code {
+    pub const ALL: &[Code] = &[
+        Code::InvalidSpec,
+        Code::InvalidSchema,
+        Code::UnsupportedReference,
+        Code::SchemaNotFound,
+        Code::DuplicateSchema,
+        Code::UnsupportedAnyOf,
+        Code::InvalidDiscriminator,
+        Code::UnsupportedObject,
+        Code::UnsupportedTuple,
+        Code::UnsupportedNumberWidth,
+        Code::UnsupportedIntegerFormat,
+        Code::UnsupportedPattern,
+        Code::UnsupportedPosition,
+        Code::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::InvalidSpec => "invalid-spec",
+            Code::InvalidSchema => "invalid-schema",
+            Code::UnsupportedReference => "unsupported-reference",
+            Code::SchemaNotFound => "schema-not-found",
+            Code::DuplicateSchema => "duplicate-schema",
+            Code::UnsupportedAnyOf => "unsupported-any-of",
+            Code::InvalidDiscriminator => "invalid-discriminator",
+            Code::UnsupportedObject => "unsupported-object",
+            Code::UnsupportedTuple => "unsupported-tuple",
+            Code::UnsupportedNumberWidth => "unsupported-number-width",
+            Code::UnsupportedIntegerFormat => "unsupported-integer-format",
+            Code::UnsupportedPattern => "unsupported-pattern",
+            Code::UnsupportedPosition => "unsupported-position",
+            Code::Other => "other",
+        }
+    }
+
+    /// A one-line description of the code, suitable for SARIF rule metadata.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Code::InvalidSpec => "The spec is not a well-formed OpenAPI document.",
+            Code::InvalidSchema => "A managed schema doesn't match any supported schema shape.",
+            Code::UnsupportedReference => "A $ref has an unsupported form.",
+            Code::SchemaNotFound => "A $ref names a schema that doesn't exist.",
+            Code::DuplicateSchema => "Two schemas would be generated with the same name.",
+            Code::UnsupportedAnyOf => {
+                "An anyOf's variants can't be represented in the target language."
+            }
+            Code::InvalidDiscriminator => {
+                "A oneOf's discriminator is inconsistent with its variants."
+            }
+            Code::UnsupportedObject => {
+                "An object schema can't be represented in the target language."
+            }
+            Code::UnsupportedTuple => "A tuple schema can't be represented in the target language.",
+            Code::UnsupportedNumberWidth => "A number schema has an unsupported width.",
+            Code::UnsupportedIntegerFormat => "An integer schema has an unsupported format.",
+            Code::UnsupportedPattern => {
+                "A string schema's pattern can't be checked in the target language."
+            }
+            Code::UnsupportedPosition => "A schema appears in a position where it is unsupported.",
+            Code::Other => "An error not attributable to the spec.",
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A JSON pointer into the OpenAPI spec, in URI fragment form (e.g.
+/// `#/components/schemas/FilterIn/prefixItems/2`), or into another file that
+/// the spec references, prefixed by the file's path (e.g.
+/// `filters.yaml#/Filter`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer(String);
+
+impl Pointer {
+    /// Returns a pointer to `fragment`, a JSON pointer, in the file at `uri`,
+    /// or in the spec if `uri` is empty.
+    pub fn new(uri: &str, fragment: &str) -> Pointer {
+        Pointer(format!("{uri}#{fragment}"))
+    }
+
+    /// Returns a pointer to the named schema in `#/components/schemas`.
+    pub fn schema(name: &str) -> Pointer {
+        Pointer("#/components/schemas".into()).join(name)
+    }
+
+    /// Returns a pointer to the child of this pointer named `segment`.
+    pub fn join(&self, segment: impl fmt::Display) -> Pointer {
+        let segment = segment.to_string().replace('~', "~0").replace('/', "~1");
+        Pointer(format!("{}/{segment}", self.0))
+    }
+
+    /// Returns the file the pointer points into, unless it's the spec.
+    pub fn file(&self) -> Option<&str> {
+        let (file, _) = self.0.split_once('#')?;
+        Some(file).filter(|file| !file.is_empty())
+    }
+
+    /// Returns the unescaped segments of the pointer.
+    pub fn segments(&self) -> impl Iterator<Item = String> {
+        let (_, fragment) = self.0.split_once('#').unwrap_or(("", &self.0));
+        fragment
+            .split('/')
+            .skip(1)
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+    }
+
+    /// Returns where this pointer points in the files that the spec was
+    /// bundled from, given the location that each schema imported into
+    /// `#/components/schemas` came from.
+    pub fn relocate(&self, imports: &BTreeMap<String, Pointer>) -> Pointer {
+        let mut segments = self.segments();
+        let origin = match (
+            self.file(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) {
+            (None, Some(components), Some(schemas), Some(name))
+                if components == "components" && schemas == "schemas" =>
+            {
+                imports.get(&name)
+            }
+            _ => None,
+        };
+        match origin {
+            Some(origin) => segments.fold(origin.clone(), |pointer, s| pointer.join(s)),
+            None => self.clone(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A problem that prevents code generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub message: String,
+    /// The location of the problem in the spec, if known.
+    pub pointer: Option<Pointer>,
+    /// The language being generated when the problem was found, if the
+    /// problem is specific to a language.
+    pub language: Option<Language>,
+}
+
+impl Diagnostic {
+    pub fn new(code: Code, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            code,
+            message: message.into(),
+            pointer: None,
+            language: None,
+        }
+    }
+
+    /// Converts an arbitrary error into a diagnostic.
+    ///
+    /// Errors that are already diagnostics are returned as is. Other errors
+    /// become diagnostics with code [`Code::Other`] whose message includes the
+    /// error's chain of sources.
+    pub fn from_error(e: Box<dyn Error>) -> Diagnostic {
+        match e.downcast::<Diagnostic>() {
+            Ok(d) => *d,
+            Err(e) => {
+                let mut message = e.to_string();
+                let mut source = e.source();
+                while let Some(e) = source {
+                    message.push_str(&format!(": {e}"));
+                    source = e.source();
+                }
+                Diagnostic::new(Code::Other, message)
+            }
+        }
+    }
+
+    /// Sets the location of the diagnostic, unless it is already set.
+    pub fn or_at(mut self, pointer: &Pointer) -> Diagnostic {
+        self.pointer.get_or_insert_with(|| pointer.clone());
+        self
+    }
+
+    /// Sets the language of the diagnostic, unless it is already set.
+    pub fn or_in(mut self, language: Language) -> Diagnostic {
+        self.language.get_or_insert(language);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pointer) = &self.pointer {
+            write!(f, "{pointer}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// A collection of diagnostics.
+///
+/// Also usable as an error, for reporting several problems at once.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Converts an arbitrary error into diagnostics.
+    ///
+    /// Errors that are already [`Diagnostics`] are returned as is; other
+    /// errors become a single diagnostic as per [`Diagnostic::from_error`].
+    pub fn from_error(e: Box<dyn Error>) -> Diagnostics {
+        match e.downcast::<Diagnostics>() {
+            Ok(d) => *d,
+            Err(e) => Diagnostics(vec![Diagnostic::from_error(e)]),
+        }
+    }
+
+    /// Adds a diagnostic, unless an identical diagnostic was already added.
+    ///
+    /// A schema that is rendered in several places (e.g., both on its own and
+    /// as a variant of a union) is thus reported only once.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        if !self.0.contains(&diagnostic) {
+            self.0.push(diagnostic);
+        }
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        for d in other.0 {
+            self.push(d);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[Diagnostic] {
+        &self.0
+    }
+
+    /// Sets the language of each diagnostic, unless it is already set.
+    pub fn or_in(self, language: Language) -> Diagnostics {
+        Diagnostics(self.0.into_iter().map(|d| d.or_in(language)).collect())
+    }
+
+    /// Moves each diagnostic in a schema that was imported into
+    /// `#/components/schemas` to where the schema came from, as per
+    /// [`Pointer::relocate`]. A schema imported from part of another is
+    /// checked as both, so this drops the diagnostics that end up identical.
+    pub fn relocate(self, imports: &BTreeMap<String, Pointer>) -> Diagnostics {
+        let mut relocated = Diagnostics::default();
+        for mut d in self.0 {
+            d.pointer = d.pointer.map(|pointer| pointer.relocate(imports));
+            relocated.push(d);
+        }
+        relocated
+    }
+
+    /// Runs `f` to render the schema at `pointer` into `buf`.
+    ///
+    /// If `f` fails, the error is recorded rather than returned, and anything
+    /// `f` wrote to `buf` is discarded, so that rendering can move on to the
+    /// next schema.
+    pub fn record<F>(&mut self, buf: &mut CodegenBuf, pointer: &Pointer, f: F)
+    where
+        F: FnOnce(&mut CodegenBuf) -> Result<(), Box<dyn Error>>,
+    {
+        let mark = buf.mark();
+        if let Err(e) = f(buf) {
+            buf.rewind(mark);
+            for d in Diagnostics::from_error(e).0 {
+                self.push(d.or_at(pointer));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, d) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{d}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
+
+/// Constructs a diagnostic located at `pointer`.
+pub fn diag(code: Code, pointer: &Pointer, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::new(code, message).or_at(pointer)
+}
+
+/// The OpenAPI spec, or another file it references, that diagnostics refer
+/// to.
+pub struct SpecFile<'a> {
+    /// The path or URL the file was read from, as it appears in pointers into
+    /// other files.
+    pub uri: &'a str,
+    pub content: &'a str,
+}
+
+/// Renders `diagnostics` in the given format.
+///
+/// `files` are the spec, if it could be loaded, followed by the other files it
+/// references. Diagnostics are additionally located to a line in these files
+/// where possible.
+pub fn render(diagnostics: &[Diagnostic], format: DiagnosticsFormat, files: &[SpecFile]) -> String {
+    let line = |d: &Diagnostic| location(d, files)?.1;
+    match format {
+        DiagnosticsFormat::Text => {
+            let mut out = String::new();
+            for d in diagnostics {
+                match d.code {
+                    Code::Other => out.push_str(&format!("error: {d}")),
+                    code => out.push_str(&format!("error[{code}]: {d}")),
+                }
+                if let Some(language) = d.language {
+                    out.push_str(&format!(" (while generating {language})"));
+                }
+                out.push('\n');
+            }
+            out
+        }
+        DiagnosticsFormat::Json => {
+            let diagnostics: Vec<_> = diagnostics
+                .iter()
+                .map(|d| {
+                    json!({
+                        "code": d.code.as_str(),
+                        "message": d.message,
+                        "pointer": d.pointer.as_ref().map(Pointer::as_str),
+                        "file": location(d, files).map(|(uri, _)| uri),
+                        "line": line(d),
+                        "language": d.language.map(|l| l.to_string()),
+                    })
+                })
+                .collect();
+            format!("{:#}\n", Value::Array(diagnostics))
+        }
+        DiagnosticsFormat::Sarif => {
+            let rules: Vec<_> = Code::ALL
+                .iter()
+                .map(|code| {
+                    json!({
+                        "id": code.as_str(),
+                        "shortDescription": { "text": code.description() },
+                    })
+                })
+                .collect();
+            let results: Vec<_> = diagnostics
+                .iter()
+                .map(|d| {
+                    let mut result = json!({
+                        "ruleId": d.code.as_str(),
+                        "ruleIndex": Code::ALL.iter().position(|c| *c == d.code),
+                        "level": "error",
+                        "message": { "text": d.message },
+                    });
+                    if let Some(pointer) = &d.pointer {
+                        let mut location = json!({
+                            "logicalLocations": [{ "fullyQualifiedName": pointer.as_str() }],
+                        });
+                        if let Some((uri, line)) = self::location(d, files) {
+                            let mut physical = json!({ "artifactLocation": { "uri": uri } });
+                            if let Some(line) = line {
+                                physical["region"] = json!({ "startLine": line });
+                            }
+                            location["physicalLocation"] = physical;
+                        }
+                        result["locations"] = json!([location]);
+                    }
+                    if let Some(language) = d.language {
+                        result["properties"] = json!({ "language": language.to_string() });
+                    }
+                    result
+                })
+                .collect();
+            let log = json!({
+                "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+                "version": "2.1.0",
+                "runs": [{
+                    "tool": {
+                        "driver": {
+                            "name": env!("CARGO_PKG_NAME"),
+                            "version": env!("CARGO_PKG_VERSION"),
+                            "rules": rules,
+                        },
+                    },
+                    "results": results,
+                }],
+            });
+            format!("{log:#}\n")
+        }
+    }
+}
+
+/// Returns the file that `d` points into, among `files` or not, and the line
+/// there, if known.
+fn location<'a>(d: &'a Diagnostic, files: &'a [SpecFile]) -> Option<(&'a str, Option<usize>)> {
+    let pointer = d.pointer.as_ref()?;
+    let uri = match pointer.file() {
+        Some(uri) => uri,
+        None => files.first()?.uri,
+    };
+    let file = files.iter().find(|file| file.uri == uri);
+    Some((uri, file.and_then(|file| locate(file.content, pointer))))
+}
+
+/// Finds the 1-based line of `content`, the spec or another file, that
+/// `pointer` refers to.
+///
+/// This is a best-effort scan that understands block-style YAML and
+/// pretty-printed JSON objects, which covers the specs we generate from. If
+/// the pointer can't be followed all the way, returns the line of the deepest
+/// location that could be found.
+fn locate(content: &str, pointer: &Pointer) -> Option<usize> {
+    // Each line's number, indentation and content, skipping blank lines,
+    // comments and lines of only JSON brackets.
+    let mut lines: Vec<(usize, usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let text = line.trim_start();
+            let is_brackets = text
+                .trim_end_matches(',')
+                .chars()
+                .all(|c| "{}[] ".contains(c));
+            if is_brackets || text.starts_with('#') {
+                return None;
+            }
+            Some((i + 1, line.len() - text.len(), text))
+        })
+        .collect();
+
+    let mut start = 0;
+    let mut end = lines.len();
+    let mut parent_indent = None;
+    let mut found = None;
+    for segment in pointer.segments() {
+        // Children are indented more than their parent, except that YAML
+        // allows a sequence's items at the same indentation as its key.
+        let is_child = |&(_, indent, text): &(usize, usize, &str)| match parent_indent {
+            None => true,
+            Some(p) => indent > p || (indent == p && text.starts_with("- ")),
+        };
+        let Some(child_indent) = lines[start..end]
+            .iter()
+            .find(|l| is_child(l))
+            .map(|&(_, indent, _)| indent)
+        else {
+            break;
+        };
+        let mut siblings = (start..end).filter(|&i| lines[i].1 == child_indent);
+        let index = match segment.parse::<usize>() {
+            Ok(n)
+                if lines[start..end]
+                    .iter()
+                    .any(|&(_, indent, text)| indent == child_indent && text.starts_with("- ")) =>
+            {
+                siblings.filter(|&i| lines[i].2.starts_with("- ")).nth(n)
+            }
+            _ => siblings.find(|&i| {
+                let text = lines[i].2;
+                [
+                    format!("{segment}:"),
+                    format!("\"{segment}\":"),
+                    format!("'{segment}':"),
+                ]
+                .iter()
+                .any(|key| text.starts_with(key.as_str()))
+            }),
+        };
+        let Some(index) = index else {
+            break;
+        };
+        found = Some(lines[index].0);
+        // The value ends at the next sibling, which for a key may only be
+        // another key, since its own sequence items may share its indentation.
+        let is_item = lines[index].2.starts_with("- ");
+        end = (index + 1..end)
+            .find(|&i| {
+                let (_, indent, text) = lines[i];
+                indent < child_indent
+                    || (indent == child_indent && (is_item || !text.starts_with("- ")))
+            })
+            .unwrap_or(end);
+        start = index + 1;
+        parent_indent = Some(child_indent);
+        if let Some(item) = lines[index].2.strip_prefix("- ") {
+            // The sequence item's content starts on the same line, indented
+            // past the dash.
+            lines[index] = (lines[index].0, child_indent + 2, item);
+            start = index;
+            parent_indent = Some(child_indent + 1);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = "\
+openapi: 3.1.0
+components:
+  schemas:
+    # A comment.
+    Filter:
+      type: array
+      prefixItems:
+      - type: string
+      - $ref: '#/components/schemas/Value'
+    Value:
+      anyOf:
+        - type: string
+        - type: object
+          properties:
+            'a/b':
+              type: string
+";
+
+    const JSON: &str = r#"{
+  "components": {
+    "schemas": {
+      "Filter": {
+        "type": "array",
+        "prefixItems": [
+          {
+            "type": "string"
+          },
+          {
+            "$ref": "Value"
+          }
+        ]
+      },
+      "Value": {
+        "type": "string"
+      }
+    }
+  }
+}
+"#;
+
+    fn locate_str(content: &str, pointer: &str) -> Option<usize> {
+        locate(content, &Pointer(pointer.into()))
+    }
+
+    #[test]
+    fn locates_yaml() {
+        assert_eq!(locate_str(YAML, "#/components/schemas/Filter"), Some(5));
+        assert_eq!(
+            locate_str(YAML, "#/components/schemas/Filter/type"),
+            Some(6)
+        );
+        let items = "#/components/schemas/Filter/prefixItems";
+        assert_eq!(locate_str(YAML, &format!("{items}/0")), Some(8));
+        assert_eq!(locate_str(YAML, &format!("{items}/1/$ref")), Some(9));
+        let variants = "#/components/schemas/Value/anyOf";
+        assert_eq!(
+            locate_str(YAML, &format!("{variants}/1/properties")),
+            Some(14)
+        );
+        let pointer = Pointer(format!("{variants}/1/properties")).join("a/b");
+        assert_eq!(locate(YAML, &pointer.join("type")), Some(16));
+    }
+
+    #[test]
+    fn locates_json() {
+        assert_eq!(
+            locate_str(JSON, "#/components/schemas/Filter/type"),
+            Some(5)
+        );
+        let pointer = "#/components/schemas/Filter/prefixItems";
+        assert_eq!(locate_str(JSON, pointer), Some(6));
+    }
+
+    #[test]
+    fn locates_the_deepest_existing_ancestor() {
+        let pointer = "#/components/schemas/Filter/missing/type";
+        assert_eq!(locate_str(YAML, pointer), Some(5));
+        assert_eq!(locate_str(YAML, "#/paths"), None);
+    }
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        vec![
+            diag(
+                Code::UnsupportedTuple,
+                &Pointer::schema("Filter").join("prefixItems"),
+                "tuple too long",
+            ),
+            Diagnostic::new(Code::Other, "something else").or_in(Language::Go),
+        ]
+    }
+
+    #[test]
+    fn renders_text() {
+        let text = render(&diagnostics(), DiagnosticsFormat::Text, &[]);
+        assert_eq!(
+            text,
+            format!(
+                "error[{}]: #/components/schemas/Filter/prefixItems: tuple too long\n\
+                 error: something else (while generating go)\n",
+                Code::UnsupportedTuple
+            )
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        let spec = SpecFile {
+            uri: "openapi.yml",
+            content: YAML,
+        };
+        let json = render(&diagnostics(), DiagnosticsFormat::Json, &[spec]);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {
+                    "code": Code::UnsupportedTuple.as_str(),
+                    "message": "tuple too long",
+                    "pointer": "#/components/schemas/Filter/prefixItems",
+                    "file": "openapi.yml",
+                    "line": 7,
+                    "language": null,
+                },
+                {
+                    "code": Code::Other.as_str(),
+                    "message": "something else",
+                    "pointer": null,
+                    "file": null,
+                    "line": null,
+                    "language": "go",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn renders_sarif() {
+        let spec = SpecFile {
+            uri: "openapi.yml",
+            content: YAML,
+        };
+        let sarif = render(&diagnostics(), DiagnosticsFormat::Sarif, &[spec]);
+        let sarif: Value = serde_json::from_str(&sarif).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["tool"]["driver"]["rules"].as_array().unwrap().len(),
+            Code::ALL.len()
+        );
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        let index = Code::ALL.iter().position(|c| *c == Code::UnsupportedTuple);
+        assert_eq!(results[0]["ruleIndex"], json!(index));
+        assert_eq!(
+            results[0]["locations"],
+            json!([{
+                "logicalLocations": [{
+                    "fullyQualifiedName": "#/components/schemas/Filter/prefixItems",
+                }],
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "openapi.yml" },
+                    "region": { "startLine": 7 },
+                },
+            }])
+        );
+        assert_eq!(results[1]["locations"], Value::Null);
+        assert_eq!(results[1]["properties"], json!({ "language": "go" }));
+    }
+
+    #[test]
+    fn relocates_imported_schemas() {
+        let origin = Pointer::new("filters.yaml", "/Filter");
+        let imports = BTreeMap::from([("Filter".to_string(), origin)]);
+        let pointer = Pointer::schema("Filter").join("prefixItems").join(0);
+        let relocated = pointer.relocate(&imports);
+        assert_eq!(relocated.as_str(), "filters.yaml#/Filter/prefixItems/0");
+        assert_eq!(relocated.file(), Some("filters.yaml"));
+        assert_eq!(
+            relocated.segments().collect::<Vec<_>>(),
+            ["Filter", "prefixItems", "0"]
+        );
+
+        let pointer = Pointer::schema("Value");
+        assert_eq!(pointer.relocate(&imports), pointer);
+        assert_eq!(pointer.file(), None);
+    }
+
+    #[test]
+    fn renders_locations_in_other_files() {
+        let files = [
+            SpecFile {
+                uri: "openapi.yml",
+                content: YAML,
+            },
+            SpecFile {
+                uri: "filters.yaml",
+                content: "Filter:\n  type: string\n",
+            },
+        ];
+        let pointer = Pointer::new("filters.yaml", "/Filter/type");
+        let diagnostics = [diag(Code::InvalidSchema, &pointer, "bad type")];
+
+        let json = render(&diagnostics, DiagnosticsFormat::Json, &files);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[0]["file"], "filters.yaml");
+        assert_eq!(json[0]["line"], 2);
+
+        let sarif = render(&diagnostics, DiagnosticsFormat::Sarif, &files);
+        let sarif: Value = serde_json::from_str(&sarif).unwrap();
+        let location = &sarif["runs"][0]["results"][0]["locations"][0];
+        assert_eq!(
+            location["physicalLocation"],
+            json!({
+                "artifactLocation": { "uri": "filters.yaml" },
+                "region": { "startLine": 2 },
+            })
+        );
+
+        let text = render(&diagnostics, DiagnosticsFormat::Text, &files);
+        assert_eq!(
+            text,
+            "error[invalid-schema]: filters.yaml#/Filter/type: bad type\n"
+        );
+    }
+}
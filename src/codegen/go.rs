@@ -1,30 +1,80 @@
-use std::{collections::BTreeMap, error::Error};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+};
 
 use crate::{
     codegen::{
-        OpenApiSchema, OpenApiSpec,
+        ConstValue, Constraint, Discriminator, IntegerFormat, OpenApiSchema, OpenApiSpec,
+        TargetOptions,
+        diagnostic::{Code, Diagnostic, Diagnostics, Pointer, diag},
+        format_bound,
         shared::{self, ConflictBehavior, TupleField},
         strip_schema_ref_prefix,
     },
     util::codegen_buf::CodegenBuf,
 };
 
-pub fn render(mut spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
-    shared::extract_any_of_tuples(&mut spec.managed_schemas, ConflictBehavior::Drop)?;
+const DEFAULT_PACKAGE: &str = "turbopuffer";
+const DEFAULT_JSON_IMPORT: &str = "github.com/turbopuffer/turbopuffer-go/v2/internal/encoding/json";
+
+pub fn render(
+    mut spec: OpenApiSpec,
+    options: &TargetOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<CodegenBuf, Box<dyn Error>> {
+    shared::extract_any_of_tuples(&mut spec, ConflictBehavior::Drop)?;
 
     let mut buf = CodegenBuf::with_indent("\t");
 
     buf.writeln("// Code generated by turbopuffer-apigen. DO NOT EDIT.");
     buf.writeln("");
-    buf.writeln("package turbopuffer");
+    let package = options.package.as_deref().unwrap_or(DEFAULT_PACKAGE);
+    let json_import = options
+        .json_import
+        .as_deref()
+        .unwrap_or(DEFAULT_JSON_IMPORT);
+    buf.writeln(format!("package {package}"));
     buf.writeln("");
-    buf.writeln(
-        r#"import shimjson "github.com/turbopuffer/turbopuffer-go/v2/internal/encoding/json""#,
-    );
+    // Only the decoders of `oneOf` schemas need `fmt`, and only some
+    // constraint checks need `regexp` and `unicode/utf8`. Go rejects unused
+    // imports.
+    let mut imports = BTreeSet::from([(json_import, format!("shimjson \"{json_import}\""))]);
+    for schema in spec.managed_schemas.values() {
+        if matches!(schema, OpenApiSchema::OneOf { .. }) {
+            imports.insert(("fmt", "\"fmt\"".into()));
+        }
+        for constraint in constructor_params(schema)
+            .into_iter()
+            .flat_map(OpenApiSchema::constraints)
+        {
+            match constraint {
+                Constraint::Pattern(_) => imports.insert(("regexp", "\"regexp\"".into())),
+                Constraint::MinLength(_) | Constraint::MaxLength(_) => {
+                    imports.insert(("unicode/utf8", "\"unicode/utf8\"".into()))
+                }
+                _ => false,
+            };
+        }
+    }
+    if imports.len() > 1 {
+        buf.writeln("import (");
+        buf.indent();
+        for (_, import) in imports {
+            buf.writeln(import);
+        }
+        buf.unindent();
+        buf.writeln(")");
+    } else {
+        buf.writeln(format!("import shimjson \"{json_import}\""));
+    }
     buf.writeln("");
 
     for (name, schema) in &spec.managed_schemas {
-        render_schema_top_level(&spec.managed_schemas, &mut buf, name, schema)?;
+        let pointer = spec.pointer(name);
+        diagnostics.record(&mut buf, &pointer, |buf| {
+            render_schema_top_level(&spec.managed_schemas, buf, name, schema, &pointer)
+        });
     }
 
     Ok(buf)
@@ -35,7 +85,9 @@ fn render_schema_top_level(
     buf: &mut CodegenBuf,
     name: &str,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    render_doc(buf, schema);
     match schema {
         OpenApiSchema::String { .. } => {
             // Top-level string schemas become a defined string type plus a
@@ -43,6 +95,7 @@ fn render_schema_top_level(
             // string value transparently.
             buf.writeln(format!("type {name} string"));
 
+            render_doc(buf, schema);
             buf.write_block(format!("func New{name}(value string) {name}"), |buf| {
                 buf.writeln(format!("return {name}(value)"));
             });
@@ -63,21 +116,23 @@ fn render_schema_top_level(
             // fields. Marshaling produces the singleton object
             // `{<Name>: <Value>}`.
             let value_schema = &**additional_properties;
+            let value_pointer = pointer.join("additionalProperties");
 
             // Struct definition.
             buf.write_block(format!("type {name} struct"), |buf| {
                 buf.writeln("Name string");
                 buf.start_line();
                 buf.write("Value ");
-                render_schema(schemas, buf, None, value_schema)?;
+                render_schema(schemas, buf, None, value_schema, &value_pointer)?;
                 buf.end_line();
                 Ok::<_, Box<dyn Error>>(())
             })?;
 
             // Constructor.
+            render_doc(buf, schema);
             buf.start_line();
             buf.write(format!("func New{name}(name string, value "));
-            render_schema(schemas, buf, None, value_schema)?;
+            render_schema(schemas, buf, None, value_schema, &value_pointer)?;
             buf.write(format!(") {name} {{"));
             buf.end_line();
             buf.indent();
@@ -101,7 +156,7 @@ fn render_schema_top_level(
         _ => {
             buf.start_line();
             buf.write(format!("type {name} "));
-            render_schema(schemas, buf, Some(name), schema)?;
+            render_schema(schemas, buf, Some(name), schema, pointer)?;
             buf.end_line();
             Ok(())
         }
@@ -131,66 +186,105 @@ fn render_schema(
     buf: &mut CodegenBuf,
     name: Option<&str>,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    if let Some(schema) = schema.without_null() {
+        return render_nullable(schemas, buf, &schema, pointer);
+    }
     match schema {
         OpenApiSchema::AnyOf {
-            _description: _,
+            description: _,
             any_of,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
-            if any_of
-                .iter()
-                .all(|s| matches!(s, OpenApiSchema::Const { .. }))
-            {
-                render_any_of_const_enum(buf, name, any_of)?;
+            if any_of.iter().all(|s| {
+                matches!(
+                    s,
+                    OpenApiSchema::Const {
+                        value: ConstValue::String(_),
+                        ..
+                    }
+                )
+            }) {
+                render_any_of_const_enum(buf, name, any_of, pointer)?;
             } else if any_of
                 .iter()
                 .all(|s| matches!(s, OpenApiSchema::Ref { .. }))
             {
-                render_any_of_refs(schemas, buf, name, any_of)?;
+                render_any_of_refs(schemas, buf, name, any_of, pointer)?;
             } else {
-                Err("unsupported anyOf")?
+                Err(diag(Code::UnsupportedAnyOf, pointer, "unsupported anyOf"))?
             }
         }
+        OpenApiSchema::OneOf {
+            description: _,
+            one_of,
+            discriminator,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_one_of(schemas, buf, name, one_of, discriminator, pointer)?,
         OpenApiSchema::Object {
-            _description: _,
+            description: _,
             _type: _,
             properties,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
             required,
         } => {
             let Some(name) = name else {
-                Err("object schema in unsupported position")?
-            };
-            if properties.len() != 1 || required.len() != 1 {
-                Err("object schemas only supported with a required property")?
+                Err(diag(
+                    Code::UnsupportedPosition,
+                    pointer,
+                    "object schema in unsupported position",
+                ))?
             };
-            let (prop_name, prop_schema) = properties.into_iter().next().unwrap();
-            if !required.contains(prop_name) {
-                Err("object schemas only supported with a single required property")?
+            let (prop_name, prop_schema) = match properties.first_key_value() {
+                Some((prop_name, prop_schema))
+                    if properties.len() == 1
+                        && required.contains(prop_name)
+                        && !matches!(prop_schema, OpenApiSchema::Const { .. }) =>
+                {
+                    (prop_name, prop_schema)
+                }
+                _ => {
+                    return render_object(
+                        schemas, buf, name, schema, properties, required, pointer,
+                    );
+                }
             };
+            let prop_pointer = pointer.join("properties").join(prop_name);
 
             let prop_name_munged = prop_name.replace("$", "");
             let prop_name_munged = shared::snake_to_camel_case(&prop_name_munged);
 
             // Struct definition.
             buf.write_block("struct", |buf| {
+                render_field_doc(buf, prop_schema, false);
                 buf.start_line();
                 buf.write(format!("{prop_name_munged} "));
-                render_schema(schemas, buf, None, prop_schema)?;
+                render_schema(schemas, buf, None, prop_schema, &prop_pointer)?;
                 buf.end_line();
                 Ok::<_, Box<dyn Error>>(())
             })?;
 
             // Constructor function.
+            let param = (prop_name_munged.as_str(), prop_schema, prop_pointer.clone());
+            render_patterns(buf, &format!("New{name}"), [param])?;
+            render_doc(buf, schema);
             buf.writeln(format!("func New{name}("));
             buf.indent();
             buf.start_line();
             buf.write(format!("{prop_name_munged} "));
-            render_schema(schemas, buf, None, prop_schema)?;
+            render_schema(schemas, buf, None, prop_schema, &prop_pointer)?;
             buf.write(",");
             buf.end_line();
             buf.unindent();
             buf.write_block(format!(") {name}"), |buf| {
+                render_checks(buf, &format!("New{name}"), &prop_name_munged, prop_schema);
                 buf.write_block(format!("return {name}"), |buf| {
                     buf.writeln(format!("{prop_name_munged}: {prop_name_munged},"));
                 })
@@ -208,49 +302,59 @@ fn render_schema(
             );
         }
         OpenApiSchema::Map {
-            _description: _,
+            description: _,
             _type: _,
             additional_properties,
             property_names: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             buf.write("map[string]");
-            render_schema(schemas, buf, None, additional_properties)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema(schemas, buf, None, additional_properties, &pointer)?;
         }
         OpenApiSchema::ArrayList {
             description,
             _type: _,
             items,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            min_items: _,
+            max_items: _,
         } => {
             buf.write("[]");
             if let Some(generic) = shared::array_list_generic(description) {
                 buf.write(generic);
             } else {
-                render_schema(schemas, buf, None, &*items)?;
+                render_schema(schemas, buf, None, items, &pointer.join("items"))?;
             }
         }
         OpenApiSchema::ArrayTuple {
-            additional_items: true,
-            ..
-        } => Err("tuple-type arrays with `items: true` unsupported")?,
-        OpenApiSchema::ArrayTuple {
-            _description: _,
+            description: _,
             _type: _,
-            additional_items: false,
+            additional_items,
             prefix_items,
             x_turbopuffer_variant_name: _,
             x_turbopuffer_variant_drop_on_conflict: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             // Since Go doesn't natively support tuples, we convert each tuple
             // to a struct with private fields and a constructor function that
             // takes the fields as arguments, in the order the tuple defines
-            // them.
+            // them. A variadic tail becomes a trailing slice field and a
+            // variadic parameter.
 
             let Some(name) = name else {
-                Err("tuple-type arrays in unsupported position")?
+                Err(diag(
+                    Code::UnsupportedPosition,
+                    pointer,
+                    "tuple-type arrays in unsupported position",
+                ))?
             };
 
             let mut prefix_items = prefix_items.clone();
@@ -262,40 +366,107 @@ fn render_schema(
                 .iter()
                 .filter(|f| !matches!(f, TupleField::Const { .. }))
                 .collect::<Vec<_>>();
+            let tail = shared::tuple_tail(additional_items.as_deref());
+            let tail_pointer = pointer.join("additionalItems");
 
             // Struct definition.
             buf.write_block(format!("{generic_decl} struct"), |buf| {
                 for field in &fields_no_consts {
-                    if let TupleField::Normal { name, schema, .. } = field {
+                    if let TupleField::Normal {
+                        name,
+                        schema,
+                        index,
+                    } = field
+                    {
+                        let pointer = pointer.join("prefixItems").join(index);
+                        render_field_doc(buf, schema, false);
                         buf.start_line();
                         buf.write(format!("{name} "));
-                        render_schema(schemas, buf, None, schema)?;
+                        render_schema(schemas, buf, None, schema, &pointer)?;
                         buf.end_line();
                     }
                 }
+                if let Some((name, schema)) = &tail {
+                    render_field_doc(buf, schema, false);
+                    buf.start_line();
+                    buf.write(format!("{name} []"));
+                    render_schema(schemas, buf, None, schema, &tail_pointer)?;
+                    buf.end_line();
+                }
                 Ok::<_, Box<dyn Error>>(())
             })?;
 
             // Constructor function.
+            let params = fields_no_consts.iter().filter_map(|field| match field {
+                TupleField::Normal {
+                    name,
+                    schema,
+                    index,
+                } => Some((
+                    name.as_str(),
+                    *schema,
+                    pointer.join("prefixItems").join(index),
+                )),
+                TupleField::Const { .. } => None,
+            });
+            let tail_param = (tail.as_ref())
+                .map(|(name, schema)| (name.as_str(), *schema, tail_pointer.clone()));
+            render_patterns(buf, &format!("New{name}"), params.chain(tail_param))?;
+            render_doc(buf, schema);
             buf.writeln(format!("func New{name}{generic_decl}("));
             buf.indent();
             for field in &fields_no_consts {
-                if let TupleField::Normal { name, schema, .. } = field {
+                if let TupleField::Normal {
+                    name,
+                    schema,
+                    index,
+                } = field
+                {
+                    let pointer = pointer.join("prefixItems").join(index);
                     buf.start_line();
                     buf.write(format!("{name} "));
-                    render_schema(schemas, buf, None, schema)?;
+                    render_schema(schemas, buf, None, schema, &pointer)?;
                     buf.write(",");
                     buf.end_line();
                 }
             }
+            if let Some((name, schema)) = &tail {
+                buf.start_line();
+                buf.write(format!("{name} ..."));
+                render_schema(schemas, buf, None, schema, &tail_pointer)?;
+                buf.write(",");
+                buf.end_line();
+            }
             buf.unindent();
             buf.write_block(format!(") {name}{generic_inst}"), |buf| {
+                for field in &fields_no_consts {
+                    if let TupleField::Normal {
+                        name: param,
+                        schema,
+                        ..
+                    } = field
+                    {
+                        render_checks(buf, &format!("New{name}"), param, schema);
+                    }
+                }
+                let checked_tail = tail
+                    .as_ref()
+                    .filter(|(_, schema)| !schema.constraints().is_empty());
+                if let Some((param, schema)) = checked_tail {
+                    buf.write_block(format!("for i := range {param}"), |buf| {
+                        let item = format!("{param}[i]");
+                        render_checks(buf, &format!("New{name}"), &item, schema);
+                    });
+                }
                 buf.write_block(format!("return {name}{generic_inst}"), |buf| {
                     for field in &fields_no_consts {
                         if let TupleField::Normal { name, .. } = field {
                             buf.writeln(format!("{name},"));
                         }
                     }
+                    if let Some((name, _)) = &tail {
+                        buf.writeln(format!("{name},"));
+                    }
                     Ok::<_, Box<dyn Error>>(())
                 })?;
                 Ok::<_, Box<dyn Error>>(())
@@ -304,69 +475,352 @@ fn render_schema(
             buf.write_block(
                 format!("func (v {name}{generic_inst}) MarshalJSON() ([]byte, error)"),
                 |buf| {
-                    buf.writeln("return shimjson.Marshal([]any{");
+                    let values = if tail.is_some() {
+                        "values := "
+                    } else {
+                        "return shimjson.Marshal("
+                    };
+                    buf.writeln(format!("{values}[]any{{"));
                     buf.indent();
                     for field in &fields {
                         match field {
-                            TupleField::Const(sconst) => {
-                                buf.writeln(format!("\"{sconst}\","));
+                            TupleField::Const(value) => {
+                                buf.writeln(format!("{},", render_const(value)));
                             }
-                            TupleField::Normal { name, schema: _ } => {
+                            TupleField::Normal { name, .. } => {
                                 buf.writeln(format!("v.{name},"));
                             }
                         }
                     }
                     buf.unindent();
-                    buf.writeln("})");
+                    match &tail {
+                        Some((name, _)) => {
+                            buf.writeln("}");
+                            buf.write_block(format!("for _, item := range v.{name}"), |buf| {
+                                buf.writeln("values = append(values, item)");
+                            });
+                            buf.writeln("return shimjson.Marshal(values)");
+                        }
+                        None => buf.writeln("})"),
+                    }
                 },
             );
         }
         OpenApiSchema::String {
-            _description: _,
+            description: _,
             _type: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            min_length: _,
+            max_length: _,
+            pattern: _,
         } => buf.write("string"),
         OpenApiSchema::Boolean {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
         } => buf.write("bool"),
         OpenApiSchema::Number {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
             x_turbopuffer_width,
         } => match x_turbopuffer_width {
             Some(32) => buf.write("float32"),
             None | Some(64) => buf.write("float64"),
-            Some(w) => Err(format!("unsupported number width: {w}"))?,
+            Some(w) => Err(diag(
+                Code::UnsupportedNumberWidth,
+                pointer,
+                format!("unsupported number width: {w}"),
+            ))?,
+        },
+        OpenApiSchema::Integer {
+            description: _,
+            _type: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
+            format,
+        } => match IntegerFormat::parse(format.as_deref(), pointer)? {
+            IntegerFormat::Unspecified => buf.write("int"),
+            IntegerFormat::Int32 => buf.write("int32"),
+            IntegerFormat::Int64 => buf.write("int64"),
+            IntegerFormat::Uint64 => buf.write("uint64"),
         },
         OpenApiSchema::Const {
-            _description: _,
-            sconst: _,
+            description: _,
+            value: _,
             title: _,
-        } => Err("const in unsupported position")?,
-        OpenApiSchema::Ref { sref, title: _ } => {
-            let name = strip_schema_ref_prefix(sref)?;
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "const in unsupported position",
+        ))?,
+        OpenApiSchema::Ref {
+            sref,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => {
+            let name = strip_schema_ref_prefix(sref, pointer)?;
             buf.write(name)
         }
+        // A pointer to an empty struct is the closest Go has to a type whose
+        // only value is null.
+        OpenApiSchema::Null { .. } => buf.write("*struct{}"),
         OpenApiSchema::Any { .. } => buf.write("any"),
     }
     Ok(())
 }
 
+/// Renders an object schema with any number of properties as a struct with an
+/// exported field for each property, except for `const` properties, which are
+/// implied by the type. Optional properties are nilable fields, which are
+/// omitted from the JSON when nil, and are left out of the constructor.
+fn render_object(
+    schemas: &BTreeMap<String, OpenApiSchema>,
+    buf: &mut CodegenBuf,
+    name: &str,
+    schema: &OpenApiSchema,
+    properties: &BTreeMap<String, OpenApiSchema>,
+    required: &[String],
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    struct Field<'a> {
+        prop_name: &'a str,
+        field_name: String,
+        param_name: String,
+        schema: &'a OpenApiSchema,
+        pointer: Pointer,
+        optional: bool,
+    }
+    let fields: Vec<_> = properties
+        .iter()
+        .filter(|(_, schema)| !matches!(schema, OpenApiSchema::Const { .. }))
+        .map(|(prop_name, schema)| {
+            let param_name = shared::snake_to_camel_case(prop_name);
+            let mut chars = param_name.chars();
+            let field_name: String = chars
+                .next()
+                .into_iter()
+                .flat_map(char::to_uppercase)
+                .chain(chars)
+                .collect();
+            Field {
+                prop_name,
+                field_name,
+                param_name,
+                schema,
+                pointer: pointer.join("properties").join(prop_name),
+                optional: !required.contains(prop_name),
+            }
+        })
+        .collect();
+    let required_fields: Vec<_> = fields.iter().filter(|f| !f.optional).collect();
+
+    // Struct definition. The tags let the struct be decoded as usual.
+    buf.write_block("struct", |buf| {
+        for field in &fields {
+            render_field_doc(buf, field.schema, field.optional);
+            buf.start_line();
+            buf.write(format!("{} ", field.field_name));
+            match field.schema.without_null() {
+                None if field.optional => {
+                    render_nullable(schemas, buf, field.schema, &field.pointer)?
+                }
+                _ => render_schema(schemas, buf, None, field.schema, &field.pointer)?,
+            }
+            let omitempty = if field.optional { ",omitempty" } else { "" };
+            buf.write(format!(" `json:\"{}{omitempty}\"`", field.prop_name));
+            buf.end_line();
+        }
+        Ok::<_, Box<dyn Error>>(())
+    })?;
+
+    // Constructor function, for the required properties.
+    let params = (required_fields.iter()).map(|field| {
+        (
+            field.param_name.as_str(),
+            field.schema,
+            field.pointer.clone(),
+        )
+    });
+    render_patterns(buf, &format!("New{name}"), params)?;
+    render_doc(buf, schema);
+    buf.writeln(format!("func New{name}("));
+    buf.indent();
+    for field in &required_fields {
+        buf.start_line();
+        buf.write(format!("{} ", field.param_name));
+        render_schema(schemas, buf, None, field.schema, &field.pointer)?;
+        buf.write(",");
+        buf.end_line();
+    }
+    buf.unindent();
+    buf.write_block(format!(") {name}"), |buf| {
+        for field in &required_fields {
+            render_checks(buf, &format!("New{name}"), &field.param_name, field.schema);
+        }
+        buf.write_block(format!("return {name}"), |buf| {
+            for field in &required_fields {
+                buf.writeln(format!("{}: {},", field.field_name, field.param_name));
+            }
+        })
+    });
+
+    // MarshalJSON, which leaves out the optional properties that are unset.
+    let write_entries = |buf: &mut CodegenBuf| {
+        buf.indent();
+        let mut fields = fields.iter();
+        for (prop_name, schema) in properties {
+            match schema {
+                OpenApiSchema::Const { value, .. } => {
+                    buf.writeln(format!("\"{prop_name}\": {},", render_const(value)));
+                }
+                _ => {
+                    let field = fields.next().unwrap();
+                    if !field.optional {
+                        buf.writeln(format!("\"{prop_name}\": v.{},", field.field_name));
+                    }
+                }
+            }
+        }
+        buf.unindent();
+    };
+    buf.write_block(
+        format!("func (v {name}) MarshalJSON() ([]byte, error)"),
+        |buf| {
+            if fields.iter().all(|f| !f.optional) {
+                buf.writeln("return shimjson.Marshal(map[string]any{");
+                write_entries(buf);
+                buf.writeln("})");
+                return;
+            }
+            buf.writeln("m := map[string]any{");
+            write_entries(buf);
+            buf.writeln("}");
+            for field in fields.iter().filter(|f| f.optional) {
+                buf.write_block(format!("if v.{} != nil", field.field_name), |buf| {
+                    buf.writeln(format!(
+                        "m[\"{}\"] = v.{}",
+                        field.prop_name, field.field_name
+                    ));
+                });
+            }
+            buf.writeln("return shimjson.Marshal(m)");
+        },
+    );
+    Ok(())
+}
+
+/// Writes the description of `schema`, if any, and its deprecation notice, if
+/// it's deprecated, as a doc comment.
+fn render_doc(buf: &mut CodegenBuf, schema: &OpenApiSchema) {
+    render_doc_paragraphs(buf, schema, None);
+}
+
+/// Like [`render_doc`], for the schema of a struct field, which also notes the
+/// schema's default, if any. Go has no default arguments, so the default only
+/// applies if an `optional` field is left nil.
+fn render_field_doc(buf: &mut CodegenBuf, schema: &OpenApiSchema, optional: bool) {
+    let note = schema.default().map(|value| match optional {
+        true => format!("Defaults to {value} when nil."),
+        false => format!("The API's default is {value}. The zero value is sent as is."),
+    });
+    render_doc_paragraphs(buf, schema, note);
+}
+
+fn render_doc_paragraphs(buf: &mut CodegenBuf, schema: &OpenApiSchema, note: Option<String>) {
+    let deprecated = schema
+        .deprecated()
+        .map(|message| format!("Deprecated: {message}"));
+    let paragraphs = [schema.description().map(str::to_string), note, deprecated];
+    for (i, paragraph) in paragraphs.iter().flatten().enumerate() {
+        // Tools only recognize the deprecation notice as a paragraph of its
+        // own.
+        if i > 0 {
+            buf.writeln("//");
+        }
+        shared::write_doc(buf, "// ", paragraph);
+    }
+}
+
+/// Renders a nullable `schema`, whose null arm has already been removed, or
+/// the schema of an optional property.
+fn render_nullable(
+    schemas: &BTreeMap<String, OpenApiSchema>,
+    buf: &mut CodegenBuf,
+    schema: &OpenApiSchema,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    // Slices, maps and interfaces can already be nil. Anything else needs a
+    // pointer.
+    let nilable = match schema {
+        OpenApiSchema::ArrayList { .. } | OpenApiSchema::Map { .. } | OpenApiSchema::Any { .. } => {
+            true
+        }
+        OpenApiSchema::Ref { sref, .. } => {
+            let name = strip_schema_ref_prefix(sref, pointer)?;
+            match schemas.get(name) {
+                Some(OpenApiSchema::AnyOf { any_of, .. }) => any_of
+                    .iter()
+                    .all(|s| matches!(s, OpenApiSchema::Ref { .. })),
+                Some(OpenApiSchema::OneOf { .. }) => true,
+                _ => false,
+            }
+        }
+        OpenApiSchema::String { .. }
+        | OpenApiSchema::Number { .. }
+        | OpenApiSchema::Integer { .. }
+        | OpenApiSchema::Boolean { .. } => false,
+        _ => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "nullable or optional schema in unsupported position",
+        ))?,
+    };
+    if !nilable {
+        buf.write("*");
+    }
+    render_schema(schemas, buf, None, schema, pointer)
+}
+
 fn render_any_of_const_enum(
     buf: &mut CodegenBuf,
     name: Option<&str>,
     schema: &[OpenApiSchema],
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
-    // When all the items in an `anyOf` are consts, we can generate a string
-    // enum for the `anyOf`.
+    // When all the items in an `anyOf` are string consts, we can generate a
+    // string enum for the `anyOf`.
     // This is a workaround for Go's lack of sum types.
 
     let Some(name) = name else {
-        Err("const enum in unsupported position")?
+        Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "const enum in unsupported position",
+        ))?
     };
 
     // Definition of the enum type.
@@ -378,9 +832,11 @@ fn render_any_of_const_enum(
     buf.indent();
     for item in schema {
         let OpenApiSchema::Const {
-            _description: _,
+            description: _,
             title: x_turbopuffer_name,
-            sconst,
+            value: ConstValue::String(sconst),
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } = item
         else {
             unreachable!("validated by render_schema");
@@ -394,6 +850,7 @@ fn render_any_of_const_enum(
             sconst_name.extend(chars);
             sconst_name
         });
+        render_doc(buf, item);
         buf.writeln(format!("{sconst_name} {name} = \"{sconst}\""))
     }
     buf.unindent();
@@ -407,13 +864,18 @@ fn render_any_of_refs(
     buf: &mut CodegenBuf,
     name: Option<&str>,
     schema: &[OpenApiSchema],
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
     // When all the items in an `anyOf` are refs, we can generate a sealed
     // interface for the `anyOf` and implement it for all the referenced types.
     // This is a workaround for Go's lack of sum types.
 
     let Some(name) = name else {
-        Err("anyOf with refs in unsupported position")?
+        Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "anyOf with refs in unsupported position",
+        ))?
     };
 
     // The name of the function that will define the sealed interface.
@@ -428,21 +890,26 @@ fn render_any_of_refs(
         buf: &mut CodegenBuf,
         fn_name: &str,
         schema: &[OpenApiSchema],
+        pointer: &Pointer,
     ) -> Result<(), Box<dyn Error>> {
-        for item in schema {
+        for (i, item) in schema.iter().enumerate() {
             let OpenApiSchema::Ref { sref, .. } = item else {
                 unreachable!("validated by render_schema");
             };
-            let sref = strip_schema_ref_prefix(sref)?;
+            let pointer = pointer.join("anyOf").join(i);
+            let sref = strip_schema_ref_prefix(sref, &pointer)?;
             match &schemas[sref] {
                 OpenApiSchema::AnyOf {
-                    _description: _,
+                    description: _,
                     any_of,
+                    title: _,
+                    deprecated: _,
+                    x_turbopuffer_deprecated_message: _,
                 } if any_of
                     .iter()
                     .all(|s| matches!(s, OpenApiSchema::Ref { .. })) =>
                 {
-                    render(schemas, buf, fn_name, any_of)?;
+                    render(schemas, buf, fn_name, any_of, &pointer)?;
                 }
                 schema => {
                     let (_generic_decl, generic_inst) =
@@ -453,7 +920,271 @@ fn render_any_of_refs(
         }
         Ok(())
     }
-    render(schemas, buf, &fn_name, schema)?;
+    render(schemas, buf, &fn_name, schema, pointer)?;
+
+    Ok(())
+}
+
+fn render_one_of(
+    schemas: &BTreeMap<String, OpenApiSchema>,
+    buf: &mut CodegenBuf,
+    name: Option<&str>,
+    one_of: &[OpenApiSchema],
+    discriminator: &Discriminator,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    // A `oneOf` is a sealed interface, like an `anyOf` of refs, plus a
+    // function that decodes any variant by switching on the discriminator.
+    // Interfaces can't have an `UnmarshalJSON` method of their own.
+
+    let Some(name) = name else {
+        Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "oneOf in unsupported position",
+        ))?
+    };
+    let variants = shared::tagged_variants(schemas, one_of, discriminator, pointer)?;
+
+    // Interface declaration and implementations.
+    let fn_name = format!("sealed_{name}");
+    buf.write_block("interface", |buf| buf.writeln(format!("{fn_name}()")));
+    for variant in &variants {
+        buf.writeln(format!("func (v {}) {fn_name}() {{}}", variant.name));
+    }
+
+    // Decoder.
+    let property = &discriminator.property_name;
+    buf.write_block(
+        format!("func Unmarshal{name}(data []byte) ({name}, error)"),
+        |buf| {
+            buf.write_block("var tagged struct", |buf| {
+                buf.writeln(format!("Tag string `json:\"{property}\"`"));
+            });
+            buf.write_block(
+                "if err := shimjson.Unmarshal(data, &tagged); err != nil",
+                |buf| buf.writeln("return nil, err"),
+            );
+            buf.writeln("switch tagged.Tag {");
+            for variant in &variants {
+                buf.writeln(format!("case \"{}\":", variant.tag));
+                buf.indent();
+                buf.writeln(format!("var v {}", variant.name));
+                buf.writeln("err := shimjson.Unmarshal(data, &v)");
+                buf.writeln("return v, err");
+                buf.unindent();
+            }
+            buf.writeln("default:");
+            buf.indent();
+            buf.writeln(format!(
+                "return nil, fmt.Errorf(\"unknown {name} {property}: %q\", tagged.Tag)"
+            ));
+            buf.unindent();
+            buf.writeln("}");
+        },
+    );
+
+    Ok(())
+}
+
+/// Returns the schemas of the parameters of the constructor generated for the
+/// top-level `schema`, if any, whose constraints the constructor checks.
+fn constructor_params(schema: &OpenApiSchema) -> Vec<&OpenApiSchema> {
+    let is_const = |schema: &&OpenApiSchema| matches!(schema, OpenApiSchema::Const { .. });
+    match schema {
+        OpenApiSchema::ArrayTuple {
+            prefix_items,
+            additional_items,
+            ..
+        } => prefix_items
+            .iter()
+            .filter(|s| !is_const(s))
+            .chain(additional_items.as_deref())
+            .collect(),
+        OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        } => properties
+            .iter()
+            .filter(|(name, schema)| required.contains(name) && !is_const(schema))
+            .map(|(_, schema)| schema)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Writes the checks of the constraints of `schema` on the parameter `param`
+/// of the constructor `func_name`. Go constructors can't return an error
+/// without getting in the way of nesting them, so they panic instead, like a
+/// failed `regexp.MustCompile`.
+fn render_checks(buf: &mut CodegenBuf, func_name: &str, param: &str, schema: &OpenApiSchema) {
+    // Nullable parameters are only checked when set.
+    let (guard, value) = match schema.without_null() {
+        None => (String::new(), param.to_string()),
+        Some(OpenApiSchema::ArrayList { .. }) => (format!("{param} != nil && "), param.to_string()),
+        Some(_) => (format!("{param} != nil && "), format!("*{param}")),
+    };
+    for constraint in schema.constraints() {
+        let violated = match constraint {
+            Constraint::MinItems(n) => format!("len({value}) < {n}"),
+            Constraint::MaxItems(n) => format!("len({value}) > {n}"),
+            Constraint::MinLength(n) => format!("utf8.RuneCountInString({value}) < {n}"),
+            Constraint::MaxLength(n) => format!("utf8.RuneCountInString({value}) > {n}"),
+            Constraint::Minimum(n) => format!("{value} < {}", format_bound(n)),
+            Constraint::ExclusiveMinimum(n) => format!("{value} <= {}", format_bound(n)),
+            Constraint::Maximum(n) => format!("{value} > {}", format_bound(n)),
+            Constraint::ExclusiveMaximum(n) => format!("{value} >= {}", format_bound(n)),
+            Constraint::Pattern(_) => {
+                format!("!{}.MatchString({value})", pattern_var(func_name, param))
+            }
+        };
+        buf.write_block(format!("if {guard}{violated}"), |buf| {
+            let message = format!("{func_name}: {}", constraint.message(param));
+            buf.writeln(format!("panic({})", quote(&message)));
+        });
+    }
+}
 
+/// Declares the patterns that the constructor `func_name` checks its
+/// parameters against, given by name, schema and location, as package
+/// variables, so that each is compiled once rather than on every call.
+///
+/// Go's `regexp` implements RE2, which lacks some of the features of the
+/// ECMA-262 regular expressions that JSON Schema patterns are written in, like
+/// lookaround, so a pattern that RE2 can't parse is reported rather than left
+/// to panic when the package is loaded.
+fn render_patterns<'a>(
+    buf: &mut CodegenBuf,
+    func_name: &str,
+    params: impl IntoIterator<Item = (&'a str, &'a OpenApiSchema, Pointer)>,
+) -> Result<(), Diagnostic> {
+    for (param, schema, pointer) in params {
+        for constraint in schema.constraints() {
+            let Constraint::Pattern(pattern) = constraint else {
+                continue;
+            };
+            if let Err(e) = regex_syntax::Parser::new().parse(pattern) {
+                let reason = match &e {
+                    regex_syntax::Error::Parse(e) => e.kind().to_string(),
+                    regex_syntax::Error::Translate(e) => e.kind().to_string(),
+                    e => e.to_string(),
+                };
+                Err(diag(
+                    Code::UnsupportedPattern,
+                    &pointer,
+                    format!("pattern {pattern} isn't supported by Go's regexp: {reason}"),
+                ))?
+            }
+            buf.writeln(format!(
+                "var {} = regexp.MustCompile({})",
+                pattern_var(func_name, param),
+                quote(pattern)
+            ));
+        }
+    }
     Ok(())
 }
+
+/// Returns the name of the package variable declared by [`render_patterns`]
+/// for the parameter `param` of the constructor `func_name`, or for an item
+/// `param[i]` of it.
+fn pattern_var(func_name: &str, param: &str) -> String {
+    let param = param.strip_suffix("[i]").unwrap_or(param);
+    let mut chars = param.chars();
+    let param: String = (chars.next().into_iter())
+        .flat_map(char::to_uppercase)
+        .chain(chars)
+        .collect();
+    format!("{}{param}Pattern", shared::lower_camel_case(func_name))
+}
+
+/// Quotes `s` as a Go string literal.
+fn quote(s: &str) -> String {
+    // JSON strings are valid Go strings.
+    serde_json::Value::from(s).to_string()
+}
+
+/// Renders a const value as a Go literal.
+fn render_const(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Null => "nil".to_string(),
+        // An untyped constant above `math.MaxInt64` doesn't fit the `int` it
+        // would default to.
+        ConstValue::UInteger(u) => format!("uint64({u})"),
+        // JSON literals are valid Go literals otherwise.
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::{CONSTS, UINT64, render_schemas};
+
+    #[test]
+    fn consts() {
+        let out = render_schemas(render, CONSTS).unwrap();
+        assert!(
+            out.contains("\t\tuint64(18446744073709551615),\n\t\t-3,\n"),
+            "{out}"
+        );
+        assert!(out.contains(r#""op": "a\nb\t$c\"\r","#), "{out}");
+    }
+
+    #[test]
+    fn uint64_bounds() {
+        let out = render_schemas(render, UINT64).unwrap();
+        assert!(out.contains("n uint64"), "{out}");
+        assert!(out.contains("\tif n < 1 {\n"), "{out}");
+        assert!(out.contains("\tif n > 100 {\n"), "{out}");
+        assert!(!out.contains("n < 0"), "{out}");
+    }
+
+    #[test]
+    fn patterns_are_compiled_once() {
+        let out = render_schemas(
+            render,
+            r#"
+FilterGlob:
+  type: array
+  prefixItems:
+    - {type: string, title: attr}
+    - {const: Glob}
+    - {type: string, title: pattern, pattern: "^[a-z*]+$"}
+  additionalItems: false
+"#,
+        )
+        .unwrap();
+        let var = "newFilterGlobPatternPattern";
+        assert!(
+            out.contains(&format!("var {var} = regexp.MustCompile(\"^[a-z*]+$\")\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!("\tif !{var}.MatchString(pattern) {{\n")),
+            "{out}"
+        );
+        assert_eq!(out.matches("regexp.MustCompile").count(), 1, "{out}");
+    }
+
+    #[test]
+    fn patterns_must_be_re2() {
+        let schemas = r#"
+FilterLook:
+  type: array
+  prefixItems:
+    - {type: string, title: attr}
+    - {const: Look}
+    - {type: string, title: value, pattern: "^(?!x)"}
+  additionalItems: false
+"#;
+        let diagnostics = render_schemas(render, schemas).unwrap_err();
+        let [d] = diagnostics.as_slice() else {
+            panic!("{diagnostics}");
+        };
+        assert_eq!(d.code, Code::UnsupportedPattern);
+        let pointer = Pointer::schema("FilterLook").join("prefixItems").join(2);
+        assert_eq!(d.pointer, Some(pointer));
+    }
+}
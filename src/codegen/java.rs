@@ -2,20 +2,35 @@ use std::{collections::BTreeMap, error::Error};
 
 use crate::{
     codegen::{
-        OpenApiSchema, OpenApiSpec,
-        shared::{self, ConflictBehavior, TupleField},
+        ConstValue, Constraint, Discriminator, IntegerFormat, OpenApiSchema, OpenApiSpec,
+        TargetOptions,
+        diagnostic::{Code, Diagnostics, Pointer, diag},
+        format_bound,
+        shared::{self, ConflictBehavior, ObjectField, TupleField, object_fields},
         strip_schema_ref_prefix,
     },
     util::codegen_buf::CodegenBuf,
 };
 
-pub fn render(mut spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
-    shared::extract_any_of_tuples(&mut spec.managed_schemas, ConflictBehavior::AppendSuffix)?;
+const DEFAULT_PACKAGE: &str = "com.turbopuffer.models.namespaces";
+const DEFAULT_CORE_PACKAGE: &str = "com.turbopuffer.core";
+
+pub fn render(
+    mut spec: OpenApiSpec,
+    options: &TargetOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<CodegenBuf, Box<dyn Error>> {
+    shared::extract_any_of_tuples(&mut spec, ConflictBehavior::AppendSuffix)?;
     let ctx = RenderCtx {
-        inherits: shared::compute_inherits(&spec.managed_schemas)?,
+        inherits: shared::compute_inherits(&spec)?,
         objects_as_tuples: shared::rewrite_single_field_objects_to_tuples(
             &mut spec.managed_schemas,
         )?,
+        pointers: spec
+            .managed_schemas
+            .keys()
+            .map(|name| (name.clone(), spec.pointer(name)))
+            .collect(),
         schemas: spec.managed_schemas,
     };
 
@@ -23,30 +38,44 @@ pub fn render(mut spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
 
     buf.writeln("// Code generated by turbopuffer-apigen. DO NOT EDIT.");
     buf.writeln("");
-    buf.writeln("package com.turbopuffer.models.namespaces");
+    // The generated code refers to its own deprecated declarations.
+    buf.writeln("@file:Suppress(\"DEPRECATION\")");
+    buf.writeln("");
+    let package = options.package.as_deref().unwrap_or(DEFAULT_PACKAGE);
+    let core_package = options
+        .core_package
+        .as_deref()
+        .unwrap_or(DEFAULT_CORE_PACKAGE);
+    buf.writeln(format!("package {package}"));
     buf.writeln("");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonFormat");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonAutoDetect");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonAutoDetect.Visibility");
+    buf.writeln("import com.fasterxml.jackson.annotation.JsonCreator");
+    buf.writeln("import com.fasterxml.jackson.annotation.JsonInclude");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonProperty");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonPropertyOrder");
     buf.writeln("import com.fasterxml.jackson.annotation.JsonValue as JsonValueAnnotation");
     buf.writeln("import com.fasterxml.jackson.core.ObjectCodec");
+    buf.writeln("import com.fasterxml.jackson.databind.JsonDeserializer");
     buf.writeln("import com.fasterxml.jackson.databind.JsonNode");
     buf.writeln("import com.fasterxml.jackson.databind.annotation.JsonDeserialize");
     buf.writeln("import com.fasterxml.jackson.databind.json.JsonMapper");
-    buf.writeln("import com.turbopuffer.core.BaseDeserializer");
-    buf.writeln("import com.turbopuffer.core.jsonMapper");
-    buf.writeln("import com.turbopuffer.core.JsonValue");
+    buf.writeln(format!("import {core_package}.BaseDeserializer"));
+    buf.writeln(format!("import {core_package}.jsonMapper"));
+    buf.writeln(format!("import {core_package}.JsonValue"));
     buf.writeln("");
     buf.writeln("val jsonMapper: JsonMapper = jsonMapper()");
     buf.writeln("");
 
     for (i, (name, schema)) in ctx.schemas.iter().enumerate() {
-        if i > 0 {
-            buf.writeln("");
-        }
-        render_schema_top_level(&ctx, &mut buf, &name, &schema)?;
+        let pointer = &ctx.pointers[name];
+        diagnostics.record(&mut buf, pointer, |buf| {
+            if i > 0 {
+                buf.writeln("");
+            }
+            render_schema_top_level(&ctx, buf, name, schema, pointer)
+        });
     }
 
     Ok(buf)
@@ -57,6 +86,8 @@ struct RenderCtx {
     /// Names of objects that have been munged into tuples, and any JSON name
     /// overrides for the fields.
     objects_as_tuples: BTreeMap<String, BTreeMap<String, String>>,
+    /// The location in the spec of each schema, for diagnostics.
+    pointers: BTreeMap<String, Pointer>,
     schemas: BTreeMap<String, OpenApiSchema>,
 }
 
@@ -65,7 +96,9 @@ fn render_schema_top_level(
     buf: &mut CodegenBuf,
     name: &str,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    render_doc(buf, schema.description(), schema.deprecated(), &[]);
     match schema {
         OpenApiSchema::ArrayList { .. } => {
             buf.start_line();
@@ -74,7 +107,7 @@ fn render_schema_top_level(
             buf.write(format!(
                 "class {name} private constructor(@JsonValueAnnotation private val items: "
             ));
-            render_schema(ctx, buf, name, schema)?;
+            render_schema(ctx, buf, name, schema, pointer)?;
             buf.write(")");
             if let Some(inherits) = ctx.inherits.get(name) {
                 buf.write(format!(" : {inherits}()"));
@@ -93,7 +126,7 @@ fn render_schema_top_level(
                 buf.writeln("@JvmSynthetic");
                 buf.start_line();
                 buf.writeln("internal fun create(items: ");
-                render_schema(ctx, buf, name, schema)?;
+                render_schema(ctx, buf, name, schema, pointer)?;
                 buf.write(format!(") : {name} = {name}(items)"));
                 buf.end_line();
                 Ok::<_, Box<dyn Error>>(())
@@ -141,12 +174,13 @@ fn render_schema_top_level(
             // takes a `name` (the JSON map key) and a `value` (the JSON map
             // value), and serialize as a singleton object `{<name>: <value>}`.
             let value_schema = &**additional_properties;
+            let value_pointer = pointer.join("additionalProperties");
 
             buf.start_line();
             buf.write(format!(
                 "class {name} private constructor(name: String, value: "
             ));
-            render_schema(ctx, buf, name, value_schema)?;
+            render_schema(ctx, buf, name, value_schema, &value_pointer)?;
             buf.write(")");
             if let Some(inherits) = ctx.inherits.get(name) {
                 buf.write(format!(" : {inherits}()"));
@@ -158,7 +192,7 @@ fn render_schema_top_level(
             buf.writeln("@JsonValueAnnotation");
             buf.start_line();
             buf.write("private val data: Map<String, ");
-            render_schema(ctx, buf, name, value_schema)?;
+            render_schema(ctx, buf, name, value_schema, &value_pointer)?;
             buf.write("> = mapOf(name to value)");
             buf.end_line();
             buf.write_block("override fun toString(): String", |buf| {
@@ -170,7 +204,7 @@ fn render_schema_top_level(
                 buf.writeln("@JvmSynthetic");
                 buf.start_line();
                 buf.write("internal fun create(name: String, value: ");
-                render_schema(ctx, buf, name, value_schema)?;
+                render_schema(ctx, buf, name, value_schema, &value_pointer)?;
                 buf.write(format!("): {name} = {name}(name, value)"));
                 buf.end_line();
                 Ok::<_, Box<dyn Error>>(())
@@ -179,7 +213,22 @@ fn render_schema_top_level(
             buf.writeln("}");
             Ok(())
         }
-        _ => render_schema(ctx, buf, name, schema),
+        OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        } => render_object_class(ctx, buf, name, properties, required, pointer),
+        OpenApiSchema::AnyOf { .. } if schema.without_null().is_some() => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "nullable schemas unsupported at top level",
+        ))?,
+        OpenApiSchema::Null { .. } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "nullable schemas unsupported at top level",
+        ))?,
+        _ => render_schema(ctx, buf, name, schema, pointer),
     }
 }
 
@@ -188,37 +237,81 @@ fn render_schema(
     buf: &mut CodegenBuf,
     name: &str,
     schema: &OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    if let Some(schema) = schema.without_null() {
+        // Only types that are referred to, rather than declared, can be made
+        // nullable.
+        if matches!(
+            schema,
+            OpenApiSchema::AnyOf { .. }
+                | OpenApiSchema::Object { .. }
+                | OpenApiSchema::ArrayTuple { .. }
+                | OpenApiSchema::Const { .. }
+        ) {
+            Err(diag(
+                Code::UnsupportedPosition,
+                pointer,
+                "nullable schema in unsupported position",
+            ))?
+        }
+        render_schema(ctx, buf, name, &schema, pointer)?;
+        buf.write("?");
+        return Ok(());
+    }
     match schema {
         OpenApiSchema::AnyOf {
-            _description: _,
+            description: _,
             any_of,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
-            if any_of
-                .iter()
-                .all(|s| matches!(s, OpenApiSchema::Const { .. }))
-            {
+            if any_of.iter().all(|s| {
+                matches!(
+                    s,
+                    OpenApiSchema::Const {
+                        value: ConstValue::String(_),
+                        ..
+                    }
+                )
+            }) {
                 render_any_of_const_enum(buf, name, any_of)?;
             } else if any_of
                 .iter()
                 .all(|s| matches!(s, OpenApiSchema::Ref { .. }))
             {
-                render_any_of_refs(ctx, buf, name, any_of)?;
+                render_any_of_refs(ctx, buf, name, any_of, pointer, None)?;
             } else {
-                Err("unsupported anyOf")?
+                Err(diag(Code::UnsupportedAnyOf, pointer, "unsupported anyOf"))?
             }
         }
-        OpenApiSchema::Object { .. } => Err("object schemas unsupported")?,
+        OpenApiSchema::OneOf {
+            description: _,
+            one_of,
+            discriminator,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_any_of_refs(ctx, buf, name, one_of, pointer, Some(discriminator))?,
+        OpenApiSchema::Object { .. } => Err(diag(
+            Code::UnsupportedObject,
+            pointer,
+            "object schemas unsupported",
+        ))?,
         OpenApiSchema::Map {
-            _description: _,
+            description: _,
             _type: _,
             additional_properties,
             property_names: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             buf.write("Map<String, ");
-            render_schema(ctx, buf, name, additional_properties)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema(ctx, buf, name, additional_properties, &pointer)?;
             buf.write(">");
         }
         OpenApiSchema::ArrayList {
@@ -226,53 +319,62 @@ fn render_schema(
             _type: _,
             items,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            min_items: _,
+            max_items: _,
         } => {
             buf.write("List<");
-            render_schema(ctx, buf, name, &*items)?;
+            render_schema(ctx, buf, name, items, &pointer.join("items"))?;
             buf.write(">");
         }
         OpenApiSchema::ArrayTuple {
-            additional_items: true,
-            ..
-        } => Err("tuple-type arrays with `items: true` unsupported")?,
-        OpenApiSchema::ArrayTuple {
-            _description: _,
+            description: _,
             _type: _,
-            additional_items: false,
+            additional_items,
             prefix_items,
             x_turbopuffer_variant_name: _,
             x_turbopuffer_variant_drop_on_conflict: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             // Since Java doesn't natively support tuples, we convert each tuple
             // to a class with private fields and a constructor function that
             // takes the fields as arguments, in the order the tuple defines
-            // them.
+            // them. A variadic tail becomes a trailing list field.
 
             let fields = shared::build_tuple_fields(prefix_items);
+            let tail = shared::tuple_tail(additional_items.as_deref());
+            let tail_pointer = pointer.join("additionalItems");
+            let overrides = ctx.objects_as_tuples.get(name);
 
             // Instruct Jackson to emit private fields too.
             buf.writeln("@JsonAutoDetect(fieldVisibility = Visibility.ANY)");
 
-            // Emit JSON property order.
-            if !ctx.objects_as_tuples.contains_key(name) {
-                buf.writeln("@JsonFormat(shape = JsonFormat.Shape.ARRAY)");
-            }
-            buf.write("@JsonPropertyOrder(");
-            let mut f_idx = 0;
-            for field in &fields {
-                match field {
-                    TupleField::Normal { name, .. } => {
-                        buf.write(format!("\"{name}\","));
-                    }
-                    _ => {
-                        buf.write(format!("\"f{f_idx}\","));
-                        f_idx += 1;
+            // Emit JSON property order. Jackson can't flatten the tail into
+            // the array, so tuples with one are serialized by `toJson`
+            // instead.
+            if tail.is_none() {
+                if !ctx.objects_as_tuples.contains_key(name) {
+                    buf.writeln("@JsonFormat(shape = JsonFormat.Shape.ARRAY)");
+                }
+                buf.write("@JsonPropertyOrder(");
+                let mut f_idx = 0;
+                for field in &fields {
+                    match field {
+                        TupleField::Normal { name, .. } => {
+                            buf.write(format!("\"{name}\","));
+                        }
+                        _ => {
+                            buf.write(format!("\"f{f_idx}\","));
+                            f_idx += 1;
+                        }
                     }
                 }
+                buf.write(")");
+                buf.end_line();
             }
-            buf.write(")");
-            buf.end_line();
 
             // Start class declaration.
             buf.start_line();
@@ -280,12 +382,23 @@ fn render_schema(
 
             // Constructor declaration.
             for field in &fields {
-                if let TupleField::Normal { name, schema } = field {
+                if let TupleField::Normal {
+                    name,
+                    schema,
+                    index,
+                } = field
+                {
+                    let pointer = shared::tuple_field_pointer(pointer, overrides, *index);
                     buf.write(format!("{name}: "));
-                    render_schema(ctx, buf, name, schema)?;
+                    render_schema(ctx, buf, name, schema, &pointer)?;
                     buf.write(", ");
                 }
             }
+            if let Some((name, schema)) = &tail {
+                buf.write(format!("{name}: List<"));
+                render_schema(ctx, buf, name, schema, &tail_pointer)?;
+                buf.write(">, ");
+            }
 
             // Inherits declaration.
             buf.write(")");
@@ -303,11 +416,10 @@ fn render_schema(
                     TupleField::Normal {
                         name: prop_name,
                         schema,
+                        index,
                     } => {
                         buf.start_line();
-                        let json_name = ctx
-                            .objects_as_tuples
-                            .get(name)
+                        let json_name = overrides
                             .and_then(|overrides| overrides.get(prop_name))
                             .map(|json_name| json_name.replace("$", "\\$"));
                         if let Some(json_name) = json_name {
@@ -324,19 +436,61 @@ fn render_schema(
                                     ));
                             }
                             _ => {
+                                let pointer =
+                                    shared::tuple_field_pointer(pointer, overrides, *index);
                                 buf.write(format!("private val {prop_name}: "));
-                                render_schema(ctx, buf, prop_name, schema)?;
+                                render_schema(ctx, buf, prop_name, schema, &pointer)?;
                                 buf.write(format!(" = {prop_name}"));
                             }
                         }
                         buf.end_line();
                     }
-                    TupleField::Const(sconst) => {
-                        buf.writeln(format!("private val f{f_idx}: String = \"{sconst}\""));
+                    TupleField::Const(value) => {
+                        let (ty, literal) = render_const(value);
+                        buf.writeln(format!("private val f{f_idx}: {ty} = {literal}"));
                         f_idx += 1;
                     }
                 }
             }
+            if let Some((name, schema)) = &tail {
+                buf.start_line();
+                buf.write(format!("private val {name}: List<"));
+                render_schema(ctx, buf, name, schema, &tail_pointer)?;
+                buf.write(format!("> = {name}"));
+                buf.end_line();
+            }
+            let params: Vec<_> = fields
+                .iter()
+                .filter_map(|field| match field {
+                    TupleField::Normal { name, schema, .. } => {
+                        Some((name.as_str(), *schema, schema.without_null().is_some()))
+                    }
+                    TupleField::Const(_) => None,
+                })
+                .collect();
+            render_checks(
+                buf,
+                &params,
+                tail.as_ref().map(|(name, schema)| (name.as_str(), *schema)),
+            );
+            if let Some((tail_name, _)) = &tail {
+                let mut values = vec![];
+                let mut f_idx = 0;
+                for field in &fields {
+                    match field {
+                        TupleField::Normal { name, .. } => values.push(name.clone()),
+                        TupleField::Const(_) => {
+                            values.push(format!("f{f_idx}"));
+                            f_idx += 1;
+                        }
+                    }
+                }
+                buf.writeln("@JsonValueAnnotation");
+                buf.writeln(format!(
+                    "private fun toJson(): List<Any?> = listOf<Any?>({}) + {tail_name}",
+                    values.join(", ")
+                ));
+            }
             buf.write_block("override fun toString(): String", |buf| {
                 buf.writeln("return jsonMapper.writeValueAsString(this)");
             });
@@ -350,9 +504,11 @@ fn render_schema(
                     new_func_vis: "internal",
                     new_func_name: "create",
                     new_func_can_use_vararg: false,
-                    class_name: &name,
-                    old_func_name: &name,
+                    class_name: name,
+                    old_func_name: name,
                     prefix_items,
+                    additional_items: additional_items.as_deref(),
+                    pointer,
                 })
             })?;
 
@@ -360,35 +516,84 @@ fn render_schema(
             buf.writeln("}");
         }
         OpenApiSchema::String {
-            _description: _,
+            description: _,
             _type: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            min_length: _,
+            max_length: _,
+            pattern: _,
         } => buf.write("String"),
         OpenApiSchema::Boolean {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
         } => buf.write("Boolean"),
         OpenApiSchema::Number {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
             x_turbopuffer_width,
         } => match x_turbopuffer_width {
             Some(32) => buf.write("Float"),
             None | Some(64) => buf.write("Double"),
-            Some(w) => Err(format!("unsupported number width: {w}"))?,
+            Some(w) => Err(diag(
+                Code::UnsupportedNumberWidth,
+                pointer,
+                format!("unsupported number width: {w}"),
+            ))?,
+        },
+        OpenApiSchema::Integer {
+            description: _,
+            _type: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
+            format,
+        } => match IntegerFormat::parse(format.as_deref(), pointer)? {
+            IntegerFormat::Int32 => buf.write("Int"),
+            IntegerFormat::Unspecified | IntegerFormat::Int64 => buf.write("Long"),
+            IntegerFormat::Uint64 => buf.write("ULong"),
         },
         OpenApiSchema::Const {
-            _description: _,
-            sconst: _,
+            description: _,
+            value: _,
             title: _,
-        } => Err("const in unsupported position")?,
-        OpenApiSchema::Ref { sref, title: _ } => {
-            let name = strip_schema_ref_prefix(sref)?;
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => Err(diag(
+            Code::UnsupportedPosition,
+            pointer,
+            "const in unsupported position",
+        ))?,
+        OpenApiSchema::Ref {
+            sref,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => {
+            let name = strip_schema_ref_prefix(sref, pointer)?;
             buf.write(name)
         }
+        OpenApiSchema::Null { .. } => buf.write("Nothing?"),
         OpenApiSchema::Any { .. } => buf.write("Any"),
     }
     Ok(())
@@ -399,16 +604,18 @@ fn render_any_of_const_enum(
     name: &str,
     schema: &[OpenApiSchema],
 ) -> Result<(), Box<dyn Error>> {
-    // When all the items in an `anyOf` are consts, we can generate a string
-    // enum for the `anyOf`.
+    // When all the items in an `anyOf` are string consts, we can generate a
+    // string enum for the `anyOf`.
     // This is a workaround for Java's lack of sum types.
 
     buf.write_block(format!("enum class {name}"), |buf| {
         for item in schema {
             let OpenApiSchema::Const {
-                _description: _,
+                description: _,
                 title: x_turbopuffer_name,
-                sconst,
+                value: ConstValue::String(sconst),
+                deprecated: _,
+                x_turbopuffer_deprecated_message: _,
             } = item
             else {
                 unreachable!("validated by render_schema");
@@ -419,7 +626,7 @@ fn render_any_of_const_enum(
                 if let Some(first_char) = chars.next() {
                     sconst_name.extend(first_char.to_uppercase());
                 }
-                while let Some(c) = chars.next() {
+                for c in chars {
                     if c.is_uppercase() {
                         sconst_name.push('_');
                     }
@@ -427,6 +634,7 @@ fn render_any_of_const_enum(
                 }
                 sconst_name
             });
+            render_doc(buf, item.description(), item.deprecated(), &[]);
             buf.writeln(format!("@JsonProperty(\"{sconst}\") {sconst_name}, "));
         }
     });
@@ -434,15 +642,155 @@ fn render_any_of_const_enum(
     Ok(())
 }
 
+/// Renders an object schema as a class with a property for each of the
+/// object's properties, except for `const` properties, which are implied by
+/// the class. Optional properties are nullable, default to null, and are
+/// omitted from the JSON when null.
+fn render_object_class(
+    ctx: &RenderCtx,
+    buf: &mut CodegenBuf,
+    name: &str,
+    properties: &BTreeMap<String, OpenApiSchema>,
+    required: &[String],
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let fields = object_fields(properties, required, pointer);
+
+    // The sealed parent's deserializer dispatches to this class, so it must
+    // not be inherited.
+    if ctx.inherits.contains_key(name) {
+        buf.writeln("@JsonDeserialize(using = JsonDeserializer.None::class)");
+    }
+
+    // Class and constructor declaration.
+    buf.start_line();
+    buf.write(format!("class {name} @JsonCreator private constructor("));
+    for field in &fields {
+        buf.write(format!("@JsonProperty(\"{}\") ", field.json_name));
+        render_object_param(ctx, buf, field)?;
+    }
+    buf.write(")");
+    if let Some(inherits) = ctx.inherits.get(name) {
+        buf.write(format!(" : {inherits}()"));
+    }
+    buf.write(" {");
+    buf.end_line();
+
+    // Class body.
+    buf.indent();
+    for (prop_name, schema) in properties {
+        let OpenApiSchema::Const { value, .. } = schema else {
+            continue;
+        };
+        let (ty, literal) = render_const(value);
+        buf.start_line();
+        buf.write(format!("@JsonProperty(\"{prop_name}\") "));
+        // The JSON mapper leaves out nulls unless told otherwise.
+        if *value == ConstValue::Null {
+            buf.write("@JsonInclude(JsonInclude.Include.ALWAYS) ");
+        }
+        buf.write(format!(
+            "private val {}: {ty} = {literal}",
+            shared::snake_to_camel_case(prop_name)
+        ));
+        buf.end_line();
+    }
+    for field in &fields {
+        render_doc(
+            buf,
+            field.schema.description(),
+            field.schema.deprecated(),
+            &[],
+        );
+        buf.start_line();
+        buf.write(format!("@JsonProperty(\"{}\") ", field.json_name));
+        if field.optional {
+            buf.write("@JsonInclude(JsonInclude.Include.NON_NULL) ");
+        }
+        buf.write(format!("val {}: ", field.name));
+        render_object_field_type(ctx, buf, field)?;
+        buf.write(format!(" = {}", field.name));
+        buf.end_line();
+    }
+    let params: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            let nullable = field.optional || field.schema.without_null().is_some();
+            (field.name.as_str(), field.schema, nullable)
+        })
+        .collect();
+    render_checks(buf, &params, None);
+    buf.write_block("override fun toString(): String", |buf| {
+        buf.writeln("return jsonMapper.writeValueAsString(this)");
+    });
+    buf.unindent();
+
+    buf.write_block("companion object", |buf| {
+        buf.writeln("@JvmSynthetic");
+        buf.start_line();
+        buf.write("internal fun create(");
+        for field in &fields {
+            render_object_param(ctx, buf, field)?;
+        }
+        buf.write(format!("): {name} = {name}("));
+        for field in &fields {
+            buf.write(format!("{},", field.name));
+        }
+        buf.write(")");
+        buf.end_line();
+        Ok::<_, Box<dyn Error>>(())
+    })?;
+
+    buf.writeln("}");
+    Ok(())
+}
+
+fn render_object_field_type(
+    ctx: &RenderCtx,
+    buf: &mut CodegenBuf,
+    field: &ObjectField,
+) -> Result<(), Box<dyn Error>> {
+    render_schema(ctx, buf, &field.name, field.schema, &field.pointer)?;
+    if field.optional && field.schema.without_null().is_none() {
+        buf.write("?");
+    }
+    Ok(())
+}
+
+/// Renders `field` as a constructor parameter, with its schema's default, if
+/// any, or else a default of null if it's optional.
+fn render_object_param(
+    ctx: &RenderCtx,
+    buf: &mut CodegenBuf,
+    field: &ObjectField,
+) -> Result<(), Box<dyn Error>> {
+    buf.write(format!("{}: ", field.name));
+    render_object_field_type(ctx, buf, field)?;
+    match render_default(field.schema, &field.pointer)? {
+        Some(default) => buf.write(format!(" = {default}")),
+        None if field.optional => buf.write(" = null"),
+        None => {}
+    }
+    buf.write(", ");
+    Ok(())
+}
+
 fn render_any_of_refs(
     ctx: &RenderCtx,
     buf: &mut CodegenBuf,
     name: &str,
     schema: &[OpenApiSchema],
+    pointer: &Pointer,
+    discriminator: Option<&Discriminator>,
 ) -> Result<(), Box<dyn Error>> {
     // When all the items in an `anyOf` are refs, we can generate a sealed
     // interface for the `anyOf` and implement it for all the referenced types.
-    // This is a workaround for Java's lack of sum types.
+    // This is a workaround for Java's lack of sum types. A `oneOf` is the
+    // same, except that its deserializer can dispatch on the discriminator.
+    let key = match discriminator {
+        Some(_) => "oneOf",
+        None => "anyOf",
+    };
 
     // Class declaration.
     let mut class_decl = format!("sealed class {name}()");
@@ -458,40 +806,73 @@ fn render_any_of_refs(
     // Methods to construct child classes.
     buf.write_block(&class_decl, |buf| {
         buf.write_block("companion object", |buf| {
-            for item in schema {
-                let OpenApiSchema::Ref { sref, title } = item else {
+            for (i, item) in schema.iter().enumerate() {
+                let OpenApiSchema::Ref { sref, title, .. } = item else {
                     unreachable!("validated by render_schema");
                 };
-                let sref = strip_schema_ref_prefix(sref)?;
+                let sref = strip_schema_ref_prefix(sref, &pointer.join(key).join(i))?;
+                let sref_pointer = &ctx.pointers[sref];
                 let subname = title.as_deref().unwrap_or(sref);
                 let new_func_name = {
                     let s = subname.strip_prefix(name).unwrap_or(subname);
-                    let s = shared::lower_camel_case(&s);
+                    let s = shared::lower_camel_case(s);
                     munge_func_name(&s)
                 };
-                match &ctx.schemas[sref] {
-                    OpenApiSchema::ArrayTuple { prefix_items, .. } => {
+                let sref_schema = &ctx.schemas[sref];
+                let description = sref_schema.description();
+                let deprecated = item.deprecated().or(sref_schema.deprecated());
+                match sref_schema {
+                    OpenApiSchema::ArrayTuple {
+                        prefix_items,
+                        additional_items,
+                        ..
+                    } => {
+                        let fields = shared::build_tuple_fields(prefix_items);
+                        let tail = shared::tuple_tail(additional_items.as_deref());
+                        let params: Vec<_> = fields
+                            .iter()
+                            .filter_map(|field| match field {
+                                TupleField::Normal { name, schema, .. } => {
+                                    Some((name.as_str(), schema.description()))
+                                }
+                                TupleField::Const(_) => None,
+                            })
+                            .chain(
+                                tail.iter()
+                                    .map(|(name, schema)| (name.as_str(), schema.description())),
+                            )
+                            .collect();
+                        render_doc(buf, description, deprecated, &params);
                         buf.writeln("@JvmStatic");
+                        // Let Java callers omit the parameters with defaults.
+                        if prefix_items.iter().any(|item| item.default().is_some()) {
+                            buf.writeln("@JvmOverloads");
+                        }
                         render_array_tuple_constructor(RenderArrayTupleConstructorParams {
                             ctx,
                             buf,
                             new_func_vis: "public",
                             new_func_name: &new_func_name,
                             new_func_can_use_vararg: true,
-                            class_name: &sref,
+                            class_name: sref,
                             old_func_name: &format!("{sref}.create"),
                             prefix_items,
+                            additional_items: additional_items.as_deref(),
+                            pointer: sref_pointer,
                         })?;
                     }
                     OpenApiSchema::ArrayList { items, .. } => {
+                        render_doc(buf, description, deprecated, &[]);
                         buf.writeln("@JvmStatic");
                         buf.start_line();
                         buf.write(format!("public fun {new_func_name}(vararg items: "));
-                        render_schema(ctx, buf, name, items)?;
+                        let pointer = sref_pointer.join("items");
+                        render_schema(ctx, buf, name, items, &pointer)?;
                         buf.write(format!(") : {sref} = {sref}.create(items.asList())"));
                         buf.end_line();
                     }
                     OpenApiSchema::String { .. } => {
+                        render_doc(buf, description, deprecated, &[]);
                         buf.writeln("@JvmStatic");
                         buf.writeln(format!(
                             "public fun {new_func_name}(value: String): {sref} = {sref}.create(value)"
@@ -502,17 +883,51 @@ fn render_any_of_refs(
                         ..
                     } => {
                         let value_schema = &**additional_properties;
+                        let value_pointer = sref_pointer.join("additionalProperties");
+                        render_doc(buf, description, deprecated, &[]);
                         buf.writeln("@JvmStatic");
                         buf.start_line();
                         buf.write(format!(
                             "public fun {new_func_name}(name: String, value: "
                         ));
-                        render_schema(ctx, buf, name, value_schema)?;
+                        render_schema(ctx, buf, name, value_schema, &value_pointer)?;
                         buf.write(format!(
                             "): {sref} = {sref}.create(name, value)"
                         ));
                         buf.end_line();
                     }
+                    OpenApiSchema::Object {
+                        properties,
+                        required,
+                        ..
+                    } => {
+                        let fields = object_fields(properties, required, sref_pointer);
+                        let params: Vec<_> = fields
+                            .iter()
+                            .map(|field| (field.name.as_str(), field.schema.description()))
+                            .collect();
+                        render_doc(buf, description, deprecated, &params);
+                        buf.writeln("@JvmStatic");
+                        // Let Java callers omit the optional properties, and
+                        // those with defaults, too.
+                        if fields
+                            .iter()
+                            .any(|field| field.optional || field.schema.default().is_some())
+                        {
+                            buf.writeln("@JvmOverloads");
+                        }
+                        buf.start_line();
+                        buf.write(format!("public fun {new_func_name}("));
+                        for field in &fields {
+                            render_object_param(ctx, buf, field)?;
+                        }
+                        buf.write(format!("): {sref} = {sref}.create("));
+                        for field in &fields {
+                            buf.write(format!("{},", field.name));
+                        }
+                        buf.write(")");
+                        buf.end_line();
+                    }
                     _ => (),
                 }
             }
@@ -521,19 +936,47 @@ fn render_any_of_refs(
 
         // Add Deserializer inner class
         buf.writeln("");
-        buf.write_block(
-            format!("    class Deserializer : BaseDeserializer<{name}>({name}::class)"),
-            |buf| {
-                buf.write_block(
-                    format!("        override fun ObjectCodec.deserialize(node: JsonNode): {name}"),
-                    |buf| {
-                        buf.writeln(format!(
-                            "            return {name}Raw(JsonValue.fromJsonNode(node))"
-                        ));
-                    },
-                );
-            },
-        );
+        if let Some(discriminator) = discriminator {
+            // Dispatch on the discriminator, falling back to the raw variant
+            // for unknown tags.
+            let variants = shared::tagged_variants(&ctx.schemas, schema, discriminator, pointer)?;
+            let property = &discriminator.property_name;
+            buf.write_block(
+                format!("class Deserializer : BaseDeserializer<{name}>({name}::class)"),
+                |buf| {
+                    buf.write_block(
+                        format!("override fun ObjectCodec.deserialize(node: JsonNode): {name}"),
+                        |buf| {
+                            buf.writeln(format!("return when (node.get(\"{property}\")?.asText()) {{"));
+                            buf.indent();
+                            for variant in &variants {
+                                buf.writeln(format!(
+                                    "\"{}\" -> treeToValue(node, {}::class.java)",
+                                    variant.tag, variant.name
+                                ));
+                            }
+                            buf.writeln(format!("else -> {name}Raw(JsonValue.fromJsonNode(node))"));
+                            buf.unindent();
+                            buf.writeln("}");
+                        },
+                    );
+                },
+            );
+        } else {
+            buf.write_block(
+                format!("    class Deserializer : BaseDeserializer<{name}>({name}::class)"),
+                |buf| {
+                    buf.write_block(
+                        format!("        override fun ObjectCodec.deserialize(node: JsonNode): {name}"),
+                        |buf| {
+                            buf.writeln(format!(
+                                "            return {name}Raw(JsonValue.fromJsonNode(node))"
+                            ));
+                        },
+                    );
+                },
+            );
+        }
 
         Ok::<_, Box<dyn Error>>(())
     })?;
@@ -564,6 +1007,9 @@ struct RenderArrayTupleConstructorParams<'a> {
     class_name: &'a str,
     old_func_name: &'a str,
     prefix_items: &'a [OpenApiSchema],
+    additional_items: Option<&'a OpenApiSchema>,
+    /// The location in the spec of the tuple schema.
+    pointer: &'a Pointer,
 }
 
 fn render_array_tuple_constructor(
@@ -576,36 +1022,63 @@ fn render_array_tuple_constructor(
         class_name,
         old_func_name,
         prefix_items,
+        additional_items,
+        pointer,
     }: RenderArrayTupleConstructorParams,
 ) -> Result<(), Box<dyn Error>> {
     let fields = shared::build_tuple_fields(prefix_items);
+    let tail = shared::tuple_tail(additional_items);
+    let overrides = ctx.objects_as_tuples.get(class_name);
 
     let normal_field_count = fields
         .iter()
         .filter(|f| matches!(f, TupleField::Normal { .. }))
         .count();
-    let use_vararg = normal_field_count == 1 && new_func_can_use_vararg;
+    let use_vararg = normal_field_count == 1 && tail.is_none() && new_func_can_use_vararg;
 
     buf.start_line();
     buf.write(format!("{new_func_vis} fun {new_func_name}("));
     for field in fields.iter() {
-        if let TupleField::Normal { name, schema } = field {
+        if let TupleField::Normal {
+            name,
+            schema,
+            index,
+        } = field
+        {
+            let pointer = shared::tuple_field_pointer(pointer, overrides, *index);
             match schema {
                 OpenApiSchema::ArrayList { items, .. } if use_vararg => {
                     buf.write(format!("vararg {name}: "));
-                    render_schema(ctx, buf, name, items)?;
+                    render_schema(ctx, buf, name, items, &pointer.join("items"))?;
                 }
                 _ => {
                     buf.write(format!("{name}: "));
-                    render_schema(ctx, buf, name, schema)?;
+                    render_schema(ctx, buf, name, schema, &pointer)?;
+                    if let Some(default) = render_default(schema, &pointer)? {
+                        buf.write(format!(" = {default}"));
+                    }
                     buf.write(", ");
                 }
             }
         }
     }
+    if let Some((name, schema)) = &tail {
+        let pointer = pointer.join("additionalItems");
+        match new_func_can_use_vararg {
+            true => {
+                buf.write(format!("vararg {name}: "));
+                render_schema(ctx, buf, name, schema, &pointer)?;
+            }
+            false => {
+                buf.write(format!("{name}: List<"));
+                render_schema(ctx, buf, name, schema, &pointer)?;
+                buf.write(">");
+            }
+        }
+    }
     buf.write(format!("): {class_name} = {old_func_name}("));
     for field in fields.iter() {
-        if let TupleField::Normal { name, schema } = field {
+        if let TupleField::Normal { name, schema, .. } = field {
             match schema {
                 OpenApiSchema::ArrayList { .. } if use_vararg => {
                     buf.write(format!("{name}.asList(),"));
@@ -614,12 +1087,194 @@ fn render_array_tuple_constructor(
             }
         }
     }
+    if let Some((name, _)) = &tail {
+        match new_func_can_use_vararg {
+            true => buf.write(format!("{name}.asList(),")),
+            false => buf.write(format!("{name},")),
+        }
+    }
     buf.write(")");
     buf.end_line();
 
     Ok(())
 }
 
+/// Writes `description` and the descriptions of the named `params`, if any,
+/// as a KDoc comment, followed by the `deprecated` message, if any, as a
+/// `@Deprecated` annotation.
+fn render_doc(
+    buf: &mut CodegenBuf,
+    description: Option<&str>,
+    deprecated: Option<&str>,
+    params: &[(&str, Option<&str>)],
+) {
+    render_kdoc(buf, description, params);
+    if let Some(message) = deprecated {
+        let message =
+            escape_kotlin_string(&message.split_whitespace().collect::<Vec<_>>().join(" "));
+        buf.writeln(format!("@Deprecated(\"{message}\")"));
+    }
+}
+
+fn render_kdoc(buf: &mut CodegenBuf, description: Option<&str>, params: &[(&str, Option<&str>)]) {
+    // Kotlin's block comments nest, so neither end nor start one.
+    let escape = |s: &str| s.trim().replace("*/", "*&#47;").replace("/*", "&#47;*");
+    let params: Vec<_> = params
+        .iter()
+        .filter_map(|(name, description)| Some((name, escape((*description)?))))
+        .collect();
+    if description.is_none() && params.is_empty() {
+        return;
+    }
+    if let (Some(description), true) = (description, params.is_empty()) {
+        let description = escape(description);
+        if !description.contains('\n')
+            && buf.indent_width() + description.len() + 7 <= shared::DOC_WIDTH
+        {
+            buf.writeln(format!("/** {description} */"));
+            return;
+        }
+    }
+    buf.writeln("/**");
+    if let Some(description) = description {
+        shared::write_doc(buf, " * ", &escape(description));
+        if !params.is_empty() {
+            buf.writeln(" *");
+        }
+    }
+    for (name, description) in params {
+        shared::write_doc(buf, " * ", &format!("@param {name} {description}"));
+    }
+    buf.writeln(" */");
+}
+
+/// Returns the Kotlin literal of the `default` of `schema` at `pointer`, if it
+/// has one.
+fn render_default(
+    schema: &OpenApiSchema,
+    pointer: &Pointer,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(value) = schema.default() else {
+        return Ok(None);
+    };
+    let schema = schema.without_null().unwrap_or_else(|| schema.clone());
+    let literal = match schema {
+        OpenApiSchema::Number {
+            x_turbopuffer_width,
+            ..
+        } => {
+            let n = value.as_f64().expect("validated when parsing");
+            match x_turbopuffer_width {
+                Some(32) => format!("{n:?}f"),
+                _ => format!("{n:?}"),
+            }
+        }
+        OpenApiSchema::Integer { format, .. } => {
+            match IntegerFormat::parse(format.as_deref(), pointer)? {
+                IntegerFormat::Int32 => value.to_string(),
+                IntegerFormat::Unspecified | IntegerFormat::Int64 => format!("{value}L"),
+                IntegerFormat::Uint64 => format!("{value}uL"),
+            }
+        }
+        _ => render_const(value).1,
+    };
+    Ok(Some(literal))
+}
+
+/// Writes an `init` block that checks the constraints of the schemas of the
+/// constructor parameters `params`, given with their names and whether they're
+/// nullable, and of each item of the variadic `tail`, if there are any
+/// constraints.
+fn render_checks(
+    buf: &mut CodegenBuf,
+    params: &[(&str, &OpenApiSchema, bool)],
+    tail: Option<(&str, &OpenApiSchema)>,
+) {
+    let params = params
+        .iter()
+        .map(|(name, schema, nullable)| (*name, *schema, *nullable, None))
+        .chain(
+            tail.map(|(tail, schema)| ("it", schema, schema.without_null().is_some(), Some(tail))),
+        );
+    let mut checks = vec![];
+    for (name, schema, nullable, tail) in params {
+        let unsigned = matches!(
+            schema.without_null().as_ref().unwrap_or(schema),
+            OpenApiSchema::Integer { format: Some(format), .. } if format == "uint64"
+        );
+        // Kotlin can't compare unsigned integers with signed ones. The bounds
+        // of unsigned integers are integers in their range, though.
+        let bound = |n: f64| match unsigned {
+            true => format!("{}uL", n as u64),
+            false => format_bound(n),
+        };
+        for constraint in schema.constraints() {
+            let check = match constraint {
+                Constraint::MinItems(n) => format!("{name}.size >= {n}"),
+                Constraint::MaxItems(n) => format!("{name}.size <= {n}"),
+                Constraint::MinLength(n) => {
+                    format!("{name}.codePointCount(0, {name}.length) >= {n}")
+                }
+                Constraint::MaxLength(n) => {
+                    format!("{name}.codePointCount(0, {name}.length) <= {n}")
+                }
+                Constraint::Minimum(n) => format!("{name} >= {}", bound(n)),
+                Constraint::ExclusiveMinimum(n) => format!("{name} > {}", bound(n)),
+                Constraint::Maximum(n) => format!("{name} <= {}", bound(n)),
+                Constraint::ExclusiveMaximum(n) => format!("{name} < {}", bound(n)),
+                Constraint::Pattern(pattern) => format!(
+                    "Regex(\"{}\").containsMatchIn({name})",
+                    escape_kotlin_string(pattern)
+                ),
+            };
+            let check = match nullable {
+                true => format!("{name} == null || {check}"),
+                false => check,
+            };
+            match tail {
+                Some(tail) => checks.push((
+                    format!("{tail}.all {{ {check} }}"),
+                    constraint.message(&format!("{tail}[i]")),
+                )),
+                None => checks.push((check, constraint.message(name))),
+            }
+        }
+    }
+    if checks.is_empty() {
+        return;
+    }
+    buf.write_block("init", |buf| {
+        for (check, message) in checks {
+            buf.writeln(format!(
+                "require({check}) {{ \"{}\" }}",
+                escape_kotlin_string(&message)
+            ));
+        }
+    });
+}
+
+/// Returns the Kotlin type and literal of a const value.
+fn render_const(value: &ConstValue) -> (&'static str, String) {
+    match value {
+        ConstValue::Null => ("Nothing?", "null".to_string()),
+        ConstValue::Bool(b) => ("Boolean", b.to_string()),
+        ConstValue::Integer(i) => ("Long", format!("{i}L")),
+        ConstValue::UInteger(u) => ("ULong", format!("{u}uL")),
+        ConstValue::Number(_) => ("Double", value.to_string()),
+        ConstValue::String(s) => ("String", format!("\"{}\"", escape_kotlin_string(s))),
+    }
+}
+
+/// Escapes `s` for inclusion in a Kotlin string literal.
+fn escape_kotlin_string(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
 fn munge_func_name(s: &str) -> String {
     match s {
         "in" => "`in`".to_string(),     // Kotlin reserved word
@@ -627,3 +1282,53 @@ fn munge_func_name(s: &str) -> String {
         _ => s.to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::{CONSTS, UINT64, render_schemas};
+
+    #[test]
+    fn consts() {
+        let out = render_schemas(render, CONSTS).unwrap();
+        assert!(
+            out.contains("private val f0: ULong = 18446744073709551615uL"),
+            "{out}"
+        );
+        assert!(out.contains("private val f1: Long = -3L"), "{out}");
+        assert!(
+            out.contains(r#"private val f0: String = "a\nb\t\$c\"\r""#),
+            "{out}"
+        );
+    }
+
+    const DEFAULTS: &str = "
+RankByOpts:
+  type: object
+  properties:
+    query: {type: string}
+    big: {type: integer, format: uint64, default: 18446744073709551615}
+  required: [query]
+";
+
+    #[test]
+    fn uint64_defaults() {
+        let out = render_schemas(render, DEFAULTS).unwrap();
+        assert!(
+            out.contains("big: ULong? = 18446744073709551615uL"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn uint64_bounds() {
+        let out = render_schemas(render, UINT64).unwrap();
+        assert!(out.contains("n: ULong"), "{out}");
+        assert!(
+            out.contains("require(n >= 1uL) { \"n must be at least 1\" }"),
+            "{out}"
+        );
+        assert!(out.contains("require(n <= 100uL)"), "{out}");
+        assert!(!out.contains("n >= 0uL"), "{out}");
+    }
+}
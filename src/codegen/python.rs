@@ -2,86 +2,166 @@ use std::collections::{BTreeSet, HashSet};
 use std::error::Error;
 
 use crate::codegen::{
-    OpenApiSchema, OpenApiSpec, SCHEMA_REF_PREFIX, shared, strip_schema_ref_prefix,
+    ConstValue, Constraint, IntegerFormat, OpenApiSchema, OpenApiSpec, SCHEMA_REF_PREFIX,
+    TargetOptions,
+    diagnostic::{Code, Diagnostics, Pointer, diag},
+    format_bound, shared, strip_schema_ref_prefix,
 };
 use crate::util::codegen_buf::CodegenBuf;
 
-pub fn render(spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
+const DEFAULT_IMPORT_PATH: &str = ".";
+
+pub fn render(
+    spec: OpenApiSpec,
+    options: &TargetOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<CodegenBuf, Box<dyn Error>> {
     let (managed_schemas, unmanaged_schemas) = topological_sort(spec)?;
 
     let mut buf = CodegenBuf::default();
     buf.writeln("# Code generated by turbopuffer-apigen. DO NOT EDIT.");
     buf.writeln("");
-    buf.writeln("from typing import Any, Tuple, Union, Literal, Mapping, Sequence, TypedDict");
+    // Only the checks of `pattern`s need `re`.
+    let has_pattern = managed_schemas.iter().any(|(name, schema)| {
+        (shared::checked_parts(name, schema).iter())
+            .flat_map(|part| part.schema.constraints())
+            .any(|constraint| matches!(constraint, Constraint::Pattern(_)))
+    });
+    if has_pattern {
+        buf.writeln("import re");
+    }
+    buf.writeln(
+        "from typing import Any, Tuple, Union, Literal, Mapping, Optional, Sequence, TypedDict",
+    );
+    buf.writeln("from typing_extensions import NotRequired, Unpack, deprecated");
     buf.writeln("");
 
+    // Each unmanaged type lives in its own module inside the import path.
+    // Relative paths (`.`, `..types`) are joined without an extra dot.
+    let import_path = options
+        .import_path
+        .as_deref()
+        .unwrap_or(DEFAULT_IMPORT_PATH);
+    let separator = if import_path.ends_with('.') { "" } else { "." };
     for name in unmanaged_schemas {
         let module_name = shared::camel_to_snake_case(&name);
-        buf.writeln(format!("from .{module_name} import {name}"));
+        buf.writeln(format!(
+            "from {import_path}{separator}{module_name} import {name}"
+        ));
     }
     buf.writeln("");
 
     for (name, schema) in managed_schemas {
-        buf.start_line();
-        buf.write(format!("{name} = "));
-        render_schema(&mut buf, &name, schema)?;
-        buf.end_line();
+        let pointer = Pointer::schema(&name);
+        diagnostics.record(&mut buf, &pointer, |buf| {
+            let validated = schema.clone();
+            if is_deprecated_class(&schema) {
+                render_deprecated_class(buf, &name, schema, &pointer)?;
+            } else {
+                let doc = docstring(&name, &schema, schema.deprecated());
+                buf.start_line();
+                buf.write(format!("{name} = "));
+                render_schema(buf, &name, schema, &pointer)?;
+                buf.end_line();
+                render_doc(buf, doc.as_deref());
+            }
+            render_validator(buf, &name, &validated);
+            Ok(())
+        });
     }
 
     Ok(buf)
 }
 
-fn topological_sort(
-    spec: OpenApiSpec,
-) -> Result<(Vec<(String, OpenApiSchema)>, BTreeSet<String>), Box<dyn Error>> {
+/// The managed schemas, ordered so that each comes after the schemas it
+/// refers to, and the unmanaged schemas they refer to.
+type SortedSchemas = (Vec<(String, OpenApiSchema)>, BTreeSet<String>);
+
+fn topological_sort(spec: OpenApiSpec) -> Result<SortedSchemas, Box<dyn Error>> {
     fn visit<'a>(
         spec: &'a OpenApiSpec,
         visited: &mut HashSet<&'a str>,
         result: &mut Vec<(String, OpenApiSchema)>,
         schema: &OpenApiSchema,
+        pointer: &Pointer,
     ) -> Result<(), Box<dyn Error>> {
         match schema {
             OpenApiSchema::Ref { sref, .. } => {
-                let name = strip_schema_ref_prefix(sref)?;
+                let name = strip_schema_ref_prefix(sref, pointer)?;
                 if spec.unmanaged_schemas.contains(name) {
                     return Ok(());
                 }
                 let Some((name, schema)) = spec.managed_schemas.get_key_value(name) else {
-                    return Err(format!("schema not found: {name}").into());
+                    Err(diag(
+                        Code::SchemaNotFound,
+                        pointer,
+                        format!("schema not found: {name}"),
+                    ))?
                 };
                 if visited.contains(name.as_str()) {
                     return Ok(());
                 }
                 visited.insert(name);
-                visit(spec, visited, result, schema)?;
+                visit(spec, visited, result, schema, &Pointer::schema(name))?;
                 result.push((name.clone(), schema.clone()))
             }
             OpenApiSchema::AnyOf {
-                _description: _,
+                description: _,
                 any_of,
+                title: _,
+                deprecated: _,
+                x_turbopuffer_deprecated_message: _,
             } => {
-                for schema in any_of {
-                    visit(spec, visited, result, schema)?;
+                for (i, schema) in any_of.iter().enumerate() {
+                    visit(
+                        spec,
+                        visited,
+                        result,
+                        schema,
+                        &pointer.join("anyOf").join(i),
+                    )?;
+                }
+            }
+            OpenApiSchema::OneOf { one_of, .. } => {
+                for (i, schema) in one_of.iter().enumerate() {
+                    visit(
+                        spec,
+                        visited,
+                        result,
+                        schema,
+                        &pointer.join("oneOf").join(i),
+                    )?;
                 }
             }
             OpenApiSchema::ArrayList { items, .. } => {
-                visit(spec, visited, result, items)?;
+                visit(spec, visited, result, items, &pointer.join("items"))?;
             }
-            OpenApiSchema::ArrayTuple { prefix_items, .. } => {
-                for schema in prefix_items {
-                    visit(spec, visited, result, schema)?;
+            OpenApiSchema::ArrayTuple {
+                prefix_items,
+                additional_items,
+                ..
+            } => {
+                for (i, schema) in prefix_items.iter().enumerate() {
+                    let pointer = pointer.join("prefixItems").join(i);
+                    visit(spec, visited, result, schema, &pointer)?;
+                }
+                if let Some(additional_items) = additional_items {
+                    let pointer = pointer.join("additionalItems");
+                    visit(spec, visited, result, additional_items, &pointer)?;
                 }
             }
             OpenApiSchema::Object { properties, .. } => {
-                for (_, schema) in properties {
-                    visit(spec, visited, result, schema)?;
+                for (name, schema) in properties {
+                    let pointer = pointer.join("properties").join(name);
+                    visit(spec, visited, result, schema, &pointer)?;
                 }
             }
             OpenApiSchema::Map {
                 additional_properties,
                 ..
             } => {
-                visit(spec, visited, result, additional_properties)?;
+                let pointer = pointer.join("additionalProperties");
+                visit(spec, visited, result, additional_properties, &pointer)?;
             }
             _ => {}
         };
@@ -95,8 +175,16 @@ fn topological_sort(
         let schema = OpenApiSchema::Ref {
             sref: format!("{SCHEMA_REF_PREFIX}{name}"),
             title: None,
+            deprecated: false,
+            x_turbopuffer_deprecated_message: None,
         };
-        visit(&spec, &mut visited, &mut result, &schema)?;
+        visit(
+            &spec,
+            &mut visited,
+            &mut result,
+            &schema,
+            &Pointer::schema(name),
+        )?;
     }
 
     Ok((result, spec.unmanaged_schemas))
@@ -106,73 +194,72 @@ fn render_schema(
     buf: &mut CodegenBuf,
     name: &str,
     schema: OpenApiSchema,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
+    if let Some(schema) = schema.without_null() {
+        buf.write("Optional[");
+        render_schema(buf, name, schema, pointer)?;
+        buf.write("]");
+        return Ok(());
+    }
     match schema {
         OpenApiSchema::AnyOf {
-            _description: _,
+            description: _,
             any_of,
-        } => {
-            let multiple = any_of.len() > 1;
-            let expanded = any_of.len() > 3;
-            if multiple {
-                buf.write("Union[");
-            }
-            if expanded {
-                buf.indent();
-                buf.end_line();
-                buf.start_line();
-            }
-            for (i, schema) in any_of.into_iter().enumerate() {
-                if i > 0 {
-                    if expanded {
-                        buf.start_line();
-                    } else {
-                        buf.write(", ");
-                    }
-                }
-                render_schema(buf, name, schema)?;
-                if expanded {
-                    buf.write(",");
-                    buf.end_line();
-                }
-            }
-            if expanded {
-                buf.unindent();
-            }
-            if multiple {
-                buf.write("]");
-            }
-        }
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_union(buf, name, any_of, &pointer.join("anyOf"))?,
+        // The variants' discriminator properties are `Literal`s, which type
+        // checkers use to narrow the union.
+        OpenApiSchema::OneOf {
+            description: _,
+            one_of,
+            discriminator: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_union(buf, name, one_of, &pointer.join("oneOf"))?,
         OpenApiSchema::Object {
-            _description: _,
+            description: _,
             _type: _,
             properties,
             required,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
-            if !properties.keys().all(|name| required.contains(name)) {
-                Err("object schemas with non-required properties not supported")?
-            };
             buf.write(format!("TypedDict(\"{name}\", {{"));
             for (i, (name, schema)) in properties.into_iter().enumerate() {
                 if i > 0 {
                     buf.write(", ");
                 }
                 buf.write(format!("\"{name}\": "));
-                render_schema(buf, &name, schema)?;
+                let optional = !required.contains(&name);
+                if optional {
+                    buf.write("NotRequired[");
+                }
+                let pointer = pointer.join("properties").join(&name);
+                render_schema(buf, &name, schema, &pointer)?;
+                if optional {
+                    buf.write("]");
+                }
             }
             buf.write("})")
         }
         OpenApiSchema::Map {
-            _description: _,
+            description: _,
             _type: _,
             additional_properties,
             property_names: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             buf.write("Mapping[str, ");
-            render_schema(buf, name, *additional_properties)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema(buf, name, *additional_properties, &pointer)?;
             buf.write("]")
         }
         OpenApiSchema::ArrayList {
@@ -180,57 +267,127 @@ fn render_schema(
             _type: _,
             items,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            min_items: _,
+            max_items: _,
         } => {
             buf.write("Sequence[");
-            render_schema(buf, name, *items)?;
+            render_schema(buf, name, *items, &pointer.join("items"))?;
             buf.write("]")
         }
         OpenApiSchema::ArrayTuple {
-            additional_items: true,
-            ..
-        } => Err("tuple-type arrays with `items: true` unsupported")?,
-        OpenApiSchema::ArrayTuple {
-            _description: _,
+            description: _,
             _type: _,
-            additional_items: false,
+            additional_items,
             prefix_items,
             x_turbopuffer_variant_name: _,
             x_turbopuffer_variant_drop_on_conflict: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             buf.write("Tuple[");
+            let prefix_len = prefix_items.len();
             for (i, schema) in prefix_items.into_iter().enumerate() {
                 if i > 0 {
                     buf.write(", ");
                 }
-                render_schema(buf, name, schema)?;
+                render_schema(buf, name, schema, &pointer.join("prefixItems").join(i))?;
+            }
+            // The unpacking syntax (`*tuple[...]`) is only valid from Python
+            // 3.11, so spell it with `Unpack` instead.
+            if let Some(additional_items) = additional_items {
+                if prefix_len > 0 {
+                    buf.write(", ");
+                }
+                buf.write("Unpack[Tuple[");
+                render_schema(
+                    buf,
+                    name,
+                    *additional_items,
+                    &pointer.join("additionalItems"),
+                )?;
+                buf.write(", ...]]");
             }
             buf.write("]")
         }
         OpenApiSchema::String {
-            _description: _,
+            description: _,
             _type: _,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            min_length: _,
+            max_length: _,
+            pattern: _,
         } => buf.write("str"),
         OpenApiSchema::Boolean {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
         } => buf.write("bool"),
         OpenApiSchema::Number {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
             x_turbopuffer_width: _,
         } => buf.write("float"),
+        OpenApiSchema::Integer {
+            description: _,
+            _type: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
+            format,
+        } => {
+            // Python's integers are arbitrary precision, but reject formats
+            // that the other languages would.
+            IntegerFormat::parse(format.as_deref(), pointer)?;
+            buf.write("int")
+        }
         OpenApiSchema::Const {
-            _description: _,
-            sconst,
+            description: _,
+            value,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => match value {
+            ConstValue::Null => buf.write("None"),
+            ConstValue::Bool(true) => buf.write("Literal[True]"),
+            ConstValue::Bool(false) => buf.write("Literal[False]"),
+            ConstValue::Integer(i) => buf.write(format!("Literal[{i}]")),
+            ConstValue::UInteger(u) => buf.write(format!("Literal[{u}]")),
+            // PEP 586 leaves floats out of `Literal`, so the best we can do is
+            // the type.
+            ConstValue::Number(_) => buf.write("float"),
+            // JSON strings are valid Python strings.
+            ConstValue::String(_) => buf.write(format!("Literal[{value}]")),
+        },
+        OpenApiSchema::Ref {
+            sref,
             title: _,
-        } => buf.write(format!("Literal[\"{sconst}\"]")),
-        OpenApiSchema::Ref { sref, title: _ } => {
-            let ref_name = strip_schema_ref_prefix(&sref)?;
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => {
+            let ref_name = strip_schema_ref_prefix(&sref, pointer)?;
             if ref_name == name {
                 // Recursive references (i.e., references to the name we're
                 // currently defining) need to be quoted.
@@ -239,7 +396,432 @@ fn render_schema(
                 buf.write(ref_name)
             }
         }
+        OpenApiSchema::Null { .. } => buf.write("None"),
         OpenApiSchema::Any { .. } => buf.write("Any"),
     }
     Ok(())
 }
+
+/// Reports whether `schema` is a deprecated object schema that can be
+/// rendered as a `TypedDict` class, i.e., one whose properties are all valid
+/// Python identifiers.
+fn is_deprecated_class(schema: &OpenApiSchema) -> bool {
+    match schema {
+        OpenApiSchema::Object { properties, .. } => {
+            schema.deprecated().is_some() && properties.keys().all(|name| is_identifier(name))
+        }
+        _ => false,
+    }
+}
+
+/// Renders the deprecated object schema `schema` as a `TypedDict` class, which,
+/// unlike a type alias, can be marked with `typing_extensions.deprecated` (see
+/// PEP 702).
+fn render_deprecated_class(
+    buf: &mut CodegenBuf,
+    name: &str,
+    schema: OpenApiSchema,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let message = schema.deprecated().unwrap_or_default().to_owned();
+    let doc = docstring(name, &schema, None);
+    let OpenApiSchema::Object {
+        properties,
+        required,
+        ..
+    } = schema
+    else {
+        unreachable!("checked by is_deprecated_class");
+    };
+
+    // JSON strings are valid Python strings.
+    buf.writeln(format!("@deprecated({})", serde_json::Value::from(message)));
+    buf.writeln(format!("class {name}(TypedDict):"));
+    buf.indent();
+    render_doc(buf, doc.as_deref());
+    if properties.is_empty() {
+        buf.writeln("pass");
+    }
+    for (prop_name, schema) in properties {
+        let optional = !required.contains(&prop_name);
+        buf.start_line();
+        buf.write(format!("{prop_name}: "));
+        if optional {
+            buf.write("NotRequired[");
+        }
+        let pointer = pointer.join("properties").join(&prop_name);
+        render_schema(buf, name, schema, &pointer)?;
+        if optional {
+            buf.write("]");
+        }
+        buf.end_line();
+    }
+    buf.unindent();
+    Ok(())
+}
+
+/// Reports whether `name` can be used as an attribute name in Python.
+fn is_identifier(name: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+        "try", "while", "with", "yield",
+    ];
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !KEYWORDS.contains(&name)
+}
+
+/// Returns the docstring for the schema `name`: its description, its default,
+/// the constraints on it and its fields, which the generated types can't
+/// check, and the deprecation notice `deprecated`, whichever it has. Type
+/// aliases can't be marked with `typing_extensions.deprecated` (see PEP 702),
+/// so for schemas not rendered by [`render_deprecated_class`] the notice is
+/// all that Python users get.
+fn docstring(name: &str, schema: &OpenApiSchema, deprecated: Option<&str>) -> Option<String> {
+    let constraints = constraint_messages(name, schema);
+    let paragraphs: Vec<String> = [
+        schema.description().map(str::to_owned),
+        schema
+            .default()
+            .map(|value| format!("Defaults to {}.", render_value(value))),
+        (!constraints.is_empty()).then(|| format!("Constraints:\n- {}", constraints.join("\n- "))),
+        deprecated.map(|message| format!("Deprecated: {message}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    (!paragraphs.is_empty()).then(|| paragraphs.join("\n\n"))
+}
+
+/// Returns the messages for the constraints on the schema `name` and on the
+/// properties or items of the object or tuple it is.
+fn constraint_messages(name: &str, schema: &OpenApiSchema) -> Vec<String> {
+    (shared::checked_parts(name, schema).iter())
+        .flat_map(|part| {
+            let constraints = part.schema.constraints();
+            constraints
+                .iter()
+                .map(|c| c.message(&part.name))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Writes `validate_<name>`, a function that checks a value of the schema
+/// `name` against the constraints on it and its parts, raising a `ValueError`
+/// for the first it violates, and otherwise returns the value. The generated
+/// types can't express the constraints, so this is the only way to find out
+/// about a violation before the server does. Writes nothing if there are no
+/// constraints.
+fn render_validator(buf: &mut CodegenBuf, name: &str, schema: &OpenApiSchema) {
+    let parts = shared::checked_parts(name, schema);
+    if parts
+        .iter()
+        .all(|part| part.schema.constraints().is_empty())
+    {
+        return;
+    }
+    let func_name = format!("validate_{}", shared::camel_to_snake_case(name));
+    buf.writeln(format!("def {func_name}(value: {name}) -> {name}:"));
+    buf.indent();
+    render_doc(
+        buf,
+        Some(&format!(
+            "Raises a ValueError if `value` violates a constraint on {name}."
+        )),
+    );
+    for part in parts {
+        let constraints = part.schema.constraints();
+        if constraints.is_empty() {
+            continue;
+        }
+        // JSON strings are valid Python strings.
+        let quote = |s: &str| serde_json::Value::from(s).to_string();
+        let (mut guard, value) = match &part.access {
+            shared::Access::Value => (String::new(), "value".to_string()),
+            shared::Access::Property { name, optional } => {
+                let guard = match optional {
+                    true => format!("{} in value and ", quote(name)),
+                    false => String::new(),
+                };
+                (guard, format!("value[{}]", quote(name)))
+            }
+            shared::Access::Item(i) => (String::new(), format!("value[{i}]")),
+            shared::Access::Tail(start) => {
+                buf.writeln(format!("for item in value[{start}:]:"));
+                buf.indent();
+                (String::new(), "item".to_string())
+            }
+        };
+        // Nullable parts are only checked when set.
+        if part.schema.without_null().is_some() {
+            guard.push_str(&format!("{value} is not None and "));
+        }
+        for constraint in constraints {
+            let violated = match constraint {
+                Constraint::MinItems(n) | Constraint::MinLength(n) => format!("len({value}) < {n}"),
+                Constraint::MaxItems(n) | Constraint::MaxLength(n) => format!("len({value}) > {n}"),
+                Constraint::Minimum(n) => format!("{value} < {}", format_bound(n)),
+                Constraint::ExclusiveMinimum(n) => format!("{value} <= {}", format_bound(n)),
+                Constraint::Maximum(n) => format!("{value} > {}", format_bound(n)),
+                Constraint::ExclusiveMaximum(n) => format!("{value} >= {}", format_bound(n)),
+                Constraint::Pattern(pattern) => {
+                    format!("re.search({}, {value}) is None", quote(pattern))
+                }
+            };
+            buf.writeln(format!("if {guard}{violated}:"));
+            buf.indent();
+            let message = constraint.message(&part.name);
+            buf.writeln(format!("raise ValueError({})", quote(&message)));
+            buf.unindent();
+        }
+        if let shared::Access::Tail(_) = part.access {
+            buf.unindent();
+        }
+    }
+    buf.writeln("return value");
+    buf.unindent();
+}
+
+/// Renders a const value as a Python literal.
+fn render_value(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Null => "None".to_string(),
+        ConstValue::Bool(true) => "True".to_string(),
+        ConstValue::Bool(false) => "False".to_string(),
+        // JSON literals are valid Python literals otherwise.
+        _ => value.to_string(),
+    }
+}
+
+/// Writes `description`, if any, as the docstring of the type alias on the
+/// preceding line, which is where editors and documentation tools look for it.
+fn render_doc(buf: &mut CodegenBuf, description: Option<&str>) {
+    let Some(description) = description else {
+        return;
+    };
+    let description = description
+        .trim()
+        .replace('\\', "\\\\")
+        .replace("\"\"\"", "\\\"\\\"\\\"");
+    if !description.contains('\n')
+        && !description.ends_with('"')
+        && buf.indent_width() + description.len() + 6 <= shared::DOC_WIDTH
+    {
+        buf.writeln(format!("\"\"\"{description}\"\"\""));
+    } else {
+        buf.writeln("\"\"\"");
+        shared::write_doc(buf, "", &description);
+        buf.writeln("\"\"\"");
+    }
+}
+
+/// Renders the `arms` of an `anyOf` or `oneOf` at `pointer` as a `Union`.
+fn render_union(
+    buf: &mut CodegenBuf,
+    name: &str,
+    arms: Vec<OpenApiSchema>,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let multiple = arms.len() > 1;
+    let expanded = arms.len() > 3;
+    if multiple {
+        buf.write("Union[");
+    }
+    if expanded {
+        buf.indent();
+        buf.end_line();
+        buf.start_line();
+    }
+    for (i, schema) in arms.into_iter().enumerate() {
+        if i > 0 {
+            if expanded {
+                buf.start_line();
+            } else {
+                buf.write(", ");
+            }
+        }
+        render_schema(buf, name, schema, &pointer.join(i))?;
+        if expanded {
+            buf.write(",");
+            buf.end_line();
+        }
+    }
+    if expanded {
+        buf.unindent();
+    }
+    if multiple {
+        buf.write("]");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::codegen::tests;
+
+    fn render_schemas(schemas: &str) -> String {
+        tests::render_schemas(render, schemas).unwrap()
+    }
+
+    #[test]
+    fn deprecated_objects_are_classes() {
+        let out = render_schemas(
+            r#"
+Legacy:
+  type: object
+  description: The old options.
+  deprecated: true
+  x-turbopuffer-deprecated-message: Use "Options" instead.
+  properties:
+    name: {type: string}
+    next: {$ref: '#/components/schemas/Legacy'}
+  required: [name]
+"#,
+        );
+        assert!(
+            out.ends_with(
+                r#"
+@deprecated("Use \"Options\" instead.")
+class Legacy(TypedDict):
+    """The old options."""
+    name: str
+    next: NotRequired["Legacy"]
+"#
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn deprecated_aliases_have_docstrings() {
+        let out = render_schemas(
+            "
+Order: {type: string, enum: [asc], deprecated: true}
+Keywords:
+  type: object
+  deprecated: true
+  properties:
+    from: {type: string}
+",
+        );
+        assert!(
+            out.ends_with(
+                r#"
+Keywords = TypedDict("Keywords", {"from": NotRequired[str]})
+"""Deprecated: Will be removed in a future version."""
+Order = Literal["asc"]
+"""Deprecated: Will be removed in a future version."""
+"#
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn consts() {
+        let out = render_schemas(tests::CONSTS);
+        assert!(out.contains("FilterBig = Tuple[str, Literal[18446744073709551615], Literal[-3]]"));
+        assert!(out.contains(r#"{"op": Literal["a\nb\t$c\"\r"]}"#), "{out}");
+    }
+
+    #[test]
+    fn defaults_are_documented() {
+        let out = render_schemas(
+            "
+Limit: {type: integer, default: 10, description: The limit.}
+Exact: {type: boolean, default: true}
+",
+        );
+        assert!(
+            out.ends_with(
+                r#"
+Exact = bool
+"""Defaults to True."""
+Limit = int
+"""
+The limit.
+
+Defaults to 10.
+"""
+"#
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn uint64_bounds() {
+        let out = render_schemas(tests::UINT64);
+        assert!(
+            out.ends_with(
+                r#"
+FilterCount = Tuple[str, Literal["Count"], int]
+"""
+Constraints:
+- n must be at least 1
+- n must be at most 100
+"""
+def validate_filter_count(value: FilterCount) -> FilterCount:
+    """Raises a ValueError if `value` violates a constraint on FilterCount."""
+    if value[2] < 1:
+        raise ValueError("n must be at least 1")
+    if value[2] > 100:
+        raise ValueError("n must be at most 100")
+    return value
+"#
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn constraints_are_checked() {
+        let out = render_schemas(
+            r#"
+Page:
+  type: object
+  properties:
+    size: {type: integer, minimum: 1}
+    cursor: {type: string, pattern: '^\d+$'}
+    note: {anyOf: [{type: string, minLength: 2}, {type: 'null'}]}
+  required: [size]
+Ranked:
+  type: array
+  prefixItems:
+    - {type: string, title: label}
+  items: {type: string, title: tag, maxLength: 8}
+"#,
+        );
+        assert!(out.contains("\nimport re\n"), "{out}");
+        assert!(
+            out.contains(
+                r#"
+    if "cursor" in value and re.search("^\\d+$", value["cursor"]) is None:
+        raise ValueError("cursor must match ^\\d+$")
+    if "note" in value and value["note"] is not None and len(value["note"]) < 2:
+        raise ValueError("note must be at least 2 characters long")
+    if value["size"] < 1:
+        raise ValueError("size must be at least 1")
+"#
+            ),
+            "{out}"
+        );
+        assert!(
+            out.contains(
+                r#"
+    for item in value[1:]:
+        if len(item) > 8:
+            raise ValueError("tag[i] must be at most 8 characters long")
+"#
+            ),
+            "{out}"
+        );
+    }
+}
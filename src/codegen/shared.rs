@@ -1,6 +1,15 @@
-use std::{collections::BTreeMap, error::Error, mem};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    mem,
+};
 
-use crate::codegen::{OpenApiSchema, SCHEMA_REF_PREFIX, strip_schema_ref_prefix};
+use crate::codegen::{
+    ConstValue, Discriminator, OpenApiSchema, OpenApiSpec, SCHEMA_REF_PREFIX,
+    diagnostic::{Code, Pointer, diag},
+    strip_schema_ref_prefix,
+};
+use crate::util::codegen_buf::CodegenBuf;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ConflictBehavior {
@@ -9,7 +18,7 @@ pub enum ConflictBehavior {
 }
 
 pub fn extract_any_of_tuples(
-    schemas: &mut BTreeMap<String, OpenApiSchema>,
+    spec: &mut OpenApiSpec,
     conflict_behavior: ConflictBehavior,
 ) -> Result<(), Box<dyn Error>> {
     // Extract named types for any extractable variants inside of a top-level
@@ -29,41 +38,52 @@ pub fn extract_any_of_tuples(
     //     attr]>`): the variant name is derived from
     //     `x-turbopuffer-variant-name` or the inner tuple's `Const` value.
 
-    let schemas_snapshot = schemas.clone();
+    let schemas_snapshot = spec.managed_schemas.clone();
+    let origins_snapshot = spec.origins.clone();
 
     let mut new_schemas = BTreeMap::new();
-    for (name, schema) in &mut *schemas {
+    for (name, schema) in &mut spec.managed_schemas {
         if let OpenApiSchema::AnyOf {
-            _description: _,
+            description: _,
             any_of,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } = schema
         {
-            if conflict_behavior == ConflictBehavior::Drop {
-                any_of.retain(|item| {
-                    !matches!(
+            let pointer = super::schema_pointer(&origins_snapshot, name).join("anyOf");
+            let is_dropped = |item: &OpenApiSchema| {
+                conflict_behavior == ConflictBehavior::Drop
+                    && matches!(
                         item,
                         OpenApiSchema::ArrayTuple {
                             x_turbopuffer_variant_drop_on_conflict: true,
                             ..
                         }
                     )
-                });
-            }
-            for item in any_of {
+            };
+            // Remember each surviving variant's index in the spec, for
+            // diagnostics.
+            let indexes: Vec<_> = (0..any_of.len())
+                .filter(|&i| !is_dropped(&any_of[i]))
+                .collect();
+            any_of.retain(|item| !is_dropped(item));
+            for (item, index) in any_of.iter_mut().zip(indexes) {
                 // If referencing an ArrayTuple with a single Const, use the
                 // const value as the title, so we can collapse Java overloads
                 // using the same logic as if the variant had been inlined.
-                if let OpenApiSchema::Ref { sref, title } = item {
-                    if title.is_none() {
-                        if let Some(target) = sref.strip_prefix(SCHEMA_REF_PREFIX) {
-                            if let Some(OpenApiSchema::ArrayTuple { prefix_items, .. }) =
-                                schemas_snapshot.get(target)
-                            {
-                                if let Some(sconst) = single_const(prefix_items) {
-                                    *title = Some(format!("{name}{}", normalize_const(sconst)));
-                                }
-                            }
+                if let OpenApiSchema::Ref { sref, title, .. } = item {
+                    let target = sref
+                        .strip_prefix(SCHEMA_REF_PREFIX)
+                        .and_then(|target| schemas_snapshot.get(target));
+                    let sconst = match target {
+                        Some(OpenApiSchema::ArrayTuple { prefix_items, .. }) if title.is_none() => {
+                            single_const(prefix_items)
                         }
+                        _ => None,
+                    };
+                    if let Some(sconst) = sconst {
+                        *title = Some(format!("{name}{}", normalize_const(sconst)));
                     }
                     continue;
                 }
@@ -115,38 +135,51 @@ pub fn extract_any_of_tuples(
                     OpenApiSchema::Ref {
                         sref: format!("{SCHEMA_REF_PREFIX}{name}"),
                         title: Some(ref_title),
+                        deprecated: false,
+                        x_turbopuffer_deprecated_message: None,
                     },
                 );
-                if new_schemas.insert(name.clone(), item).is_some() {
-                    Err(format!(
-                        "extraction of variants from anyOf failed: duplicate schema name: {name}"
+                let item_pointer = pointer.join(index);
+                if new_schemas
+                    .insert(name.clone(), (item, item_pointer.clone()))
+                    .is_some()
+                {
+                    Err(diag(
+                        Code::DuplicateSchema,
+                        &item_pointer,
+                        format!(
+                            "extraction of variants from anyOf failed: duplicate schema name: {name}"
+                        ),
                     ))?
                 }
             }
         }
     }
-    for (name, schema) in new_schemas {
-        if schemas.insert(name.clone(), schema).is_some() {
-            Err(format!(
-                "extraction of variants from anyOf failed: duplicate schema name: {name}"
+    for (name, (schema, pointer)) in new_schemas {
+        if spec.managed_schemas.insert(name.clone(), schema).is_some() {
+            Err(diag(
+                Code::DuplicateSchema,
+                &pointer,
+                format!("extraction of variants from anyOf failed: duplicate schema name: {name}"),
             ))?
         }
+        spec.origins.insert(name, pointer);
     }
     Ok(())
 }
 
 /// Returns the value of the only `Const` in `prefix_items`, or `None` if there
-/// is no `Const` or more than one.
+/// is no `Const`, more than one, or its value is not a string.
 fn single_const(prefix_items: &[OpenApiSchema]) -> Option<&str> {
-    let mut sconsts = prefix_items.iter().filter_map(|item| match item {
-        OpenApiSchema::Const { sconst, .. } => Some(sconst.as_str()),
+    let mut values = prefix_items.iter().filter_map(|item| match item {
+        OpenApiSchema::Const { value, .. } => Some(value),
         _ => None,
     });
-    let sconst = sconsts.next()?;
-    if sconsts.next().is_some() {
+    let value = values.next()?;
+    if values.next().is_some() {
         return None;
     }
-    Some(sconst)
+    value.as_str()
 }
 
 #[derive(Debug, Clone)]
@@ -154,8 +187,10 @@ pub enum TupleField<'a> {
     Normal {
         name: String,
         schema: &'a OpenApiSchema,
+        /// The index of the field in the tuple's `prefixItems`.
+        index: usize,
     },
-    Const(&'a str),
+    Const(&'a ConstValue),
 }
 
 fn build_tuple_fields_inner<'a>(
@@ -165,8 +200,8 @@ fn build_tuple_fields_inner<'a>(
 ) {
     for item in prefix_items {
         match item {
-            OpenApiSchema::Const { sconst, .. } => {
-                out.push(TupleField::Const(sconst));
+            OpenApiSchema::Const { value, .. } => {
+                out.push(TupleField::Const(value));
             }
             _ => {
                 out.push(TupleField::Normal {
@@ -175,6 +210,7 @@ fn build_tuple_fields_inner<'a>(
                         None => format!("f{i}"),
                     },
                     schema: item,
+                    index: *i,
                 });
             }
         }
@@ -188,10 +224,18 @@ pub fn build_tuple_fields(prefix_items: &[OpenApiSchema]) -> Vec<TupleField<'_>>
     fields
 }
 
+/// Returns the field name and item schema of a tuple's variadic tail, if it
+/// has one. The name is the title of the item schema, if any, or `rest`.
+pub fn tuple_tail(additional_items: Option<&OpenApiSchema>) -> Option<(String, &OpenApiSchema)> {
+    let schema = additional_items?;
+    let name = schema.title().unwrap_or("rest").to_string();
+    Some((name, schema))
+}
+
 pub fn lower_camel_case(input: &str) -> String {
     let mut s = String::new();
     let mut chars = input.chars();
-    while let Some(c) = chars.next() {
+    for c in chars.by_ref() {
         s.extend(c.to_lowercase());
         if !c.is_uppercase() {
             break;
@@ -241,25 +285,28 @@ pub fn snake_to_camel_case(input: &str) -> String {
     s
 }
 
-/// For each `anyOf`-of-`$ref` schema, records which variant inherits from
-/// which abstract base. The result maps each variant schema's name to its
-/// enclosing union's name.
-pub fn compute_inherits(
-    schemas: &BTreeMap<String, OpenApiSchema>,
-) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+/// For each `anyOf`-of-`$ref` and `oneOf` schema, records which variant
+/// inherits from which abstract base. The result maps each variant schema's
+/// name to its enclosing union's name.
+pub fn compute_inherits(spec: &OpenApiSpec) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
     let mut result = BTreeMap::new();
-    for (name, schema) in schemas {
-        let OpenApiSchema::AnyOf { any_of, .. } = schema else {
-            continue;
+    for (name, schema) in &spec.managed_schemas {
+        let (key, arms) = match schema {
+            OpenApiSchema::AnyOf { any_of, .. } => ("anyOf", any_of),
+            OpenApiSchema::OneOf { one_of, .. } => ("oneOf", one_of),
+            _ => continue,
         };
-        for item in any_of {
+        for (i, item) in arms.iter().enumerate() {
             let OpenApiSchema::Ref { sref, .. } = item else {
                 continue;
             };
-            let sref = strip_schema_ref_prefix(sref)?;
+            let pointer = spec.pointer(name).join(key).join(i);
+            let sref = strip_schema_ref_prefix(sref, &pointer)?;
             if let Some(existing) = result.insert(sref.into(), name.into()) {
-                Err(format!(
-                    "duplicate inheritance for {sref}: {existing} and {name}"
+                Err(diag(
+                    Code::DuplicateSchema,
+                    &pointer,
+                    format!("duplicate inheritance for {sref}: {existing} and {name}"),
                 ))?
             }
         }
@@ -270,15 +317,29 @@ pub fn compute_inherits(
 /// Rewrites object schemas with a single required field as 1-tuples, returning
 /// a map from each rewritten schema's name to a `pascal -> original` JSON
 /// property-name override map (so emitters that serialize tuples as objects
-/// can recover the original key).
+/// can recover the original key). Variants of `oneOf` schemas are left alone,
+/// as their sole field may be the discriminator.
 pub fn rewrite_single_field_objects_to_tuples(
     schemas: &mut BTreeMap<String, OpenApiSchema>,
 ) -> Result<BTreeMap<String, BTreeMap<String, String>>, Box<dyn Error>> {
+    let mut variants = BTreeSet::new();
+    for schema in schemas.values() {
+        if let OpenApiSchema::OneOf { .. } = schema {
+            schema.collect_refs(&mut variants);
+        }
+    }
+
     let mut names = BTreeMap::new();
     for (name, schema) in schemas {
+        if variants.contains(name) {
+            continue;
+        }
         let OpenApiSchema::Object {
+            description,
             properties,
             required,
+            deprecated,
+            x_turbopuffer_deprecated_message,
             ..
         } = schema
         else {
@@ -299,12 +360,14 @@ pub fn rewrite_single_field_objects_to_tuples(
         }
         *schema = OpenApiSchema::ArrayTuple {
             prefix_items: vec![prop_schema],
-            _description: None,
+            description: description.take(),
             _type: Default::default(),
-            additional_items: false,
+            additional_items: None,
             x_turbopuffer_variant_name: None,
             x_turbopuffer_variant_drop_on_conflict: false,
             title: None,
+            deprecated: *deprecated,
+            x_turbopuffer_deprecated_message: x_turbopuffer_deprecated_message.take(),
         };
         names.insert(
             name.clone(),
@@ -314,13 +377,249 @@ pub fn rewrite_single_field_objects_to_tuples(
     Ok(names)
 }
 
+/// Returns the location in the spec of the tuple field at `index` in the tuple
+/// at `pointer`. `overrides` is the tuple's entry in the result of
+/// [`rewrite_single_field_objects_to_tuples`], if any, in which case the field
+/// is really the object's sole property.
+pub fn tuple_field_pointer(
+    pointer: &Pointer,
+    overrides: Option<&BTreeMap<String, String>>,
+    index: usize,
+) -> Pointer {
+    match overrides.and_then(|o| o.values().next()) {
+        Some(prop_name) => pointer.join("properties").join(prop_name),
+        None => pointer.join("prefixItems").join(index),
+    }
+}
+
+/// A non-`const` property of an object schema.
+pub struct ObjectField<'a> {
+    pub json_name: &'a str,
+    /// The camel-cased name of the property.
+    pub name: String,
+    pub schema: &'a OpenApiSchema,
+    pub pointer: Pointer,
+    pub optional: bool,
+}
+
+/// Returns the non-`const` properties of the object schema at `pointer`,
+/// required properties first, as C# requires of constructor parameters.
+pub fn object_fields<'a>(
+    properties: &'a BTreeMap<String, OpenApiSchema>,
+    required: &[String],
+    pointer: &Pointer,
+) -> Vec<ObjectField<'a>> {
+    let mut fields: Vec<_> = properties
+        .iter()
+        .filter(|(_, schema)| !matches!(schema, OpenApiSchema::Const { .. }))
+        .map(|(prop_name, schema)| ObjectField {
+            json_name: prop_name,
+            name: snake_to_camel_case(prop_name),
+            schema,
+            pointer: pointer.join("properties").join(prop_name),
+            optional: !required.contains(prop_name),
+        })
+        .collect();
+    fields.sort_by_key(|field| field.optional);
+    fields
+}
+
+/// A variant of a `oneOf` schema.
+pub struct TaggedVariant<'a> {
+    /// The name of the variant's schema.
+    pub name: &'a str,
+    /// The value of the discriminator property that identifies the variant.
+    pub tag: &'a str,
+}
+
+/// Returns the variants of the named `oneOf` schema with their tags, which
+/// [`OpenApiSpec::parse`] pinned onto the variants' discriminator properties.
+pub fn tagged_variants<'a>(
+    schemas: &'a BTreeMap<String, OpenApiSchema>,
+    one_of: &'a [OpenApiSchema],
+    discriminator: &Discriminator,
+    pointer: &Pointer,
+) -> Result<Vec<TaggedVariant<'a>>, Box<dyn Error>> {
+    let mut variants = vec![];
+    for (i, arm) in one_of.iter().enumerate() {
+        let pointer = pointer.join("oneOf").join(i);
+        let tag = match arm {
+            OpenApiSchema::Ref { sref, .. } => {
+                let name = strip_schema_ref_prefix(sref, &pointer)?;
+                match schemas.get_key_value(name) {
+                    Some((name, OpenApiSchema::Object { properties, .. })) => {
+                        match properties.get(&discriminator.property_name) {
+                            Some(OpenApiSchema::Const {
+                                value: ConstValue::String(sconst),
+                                ..
+                            }) => Some((name, sconst)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let Some((name, tag)) = tag else {
+            Err(diag(
+                Code::InvalidDiscriminator,
+                &pointer,
+                "oneOf variant is not a tagged object",
+            ))?
+        };
+        variants.push(TaggedVariant { name, tag });
+    }
+    Ok(variants)
+}
+
+/// A part of a value of a schema whose constraints are checked.
+pub struct CheckedPart<'a> {
+    /// The name of the part in error messages.
+    pub name: String,
+    pub access: Access,
+    pub schema: &'a OpenApiSchema,
+}
+
+/// Where a [`CheckedPart`] is in a value.
+pub enum Access {
+    /// The value itself.
+    Value,
+    /// A property of an object, which may be absent if it's optional.
+    Property { name: String, optional: bool },
+    /// An item of a tuple.
+    Item(usize),
+    /// Each item of a tuple from the given index on.
+    Tail(usize),
+}
+
+/// Returns the parts of a value of the schema `name` whose constraints are
+/// checked: the value itself, and the properties or items of the object or
+/// tuple it is.
+pub fn checked_parts<'a>(name: &str, schema: &'a OpenApiSchema) -> Vec<CheckedPart<'a>> {
+    let mut parts = vec![CheckedPart {
+        name: name.to_string(),
+        access: Access::Value,
+        schema,
+    }];
+    match schema {
+        OpenApiSchema::Object {
+            properties,
+            required,
+            ..
+        } => {
+            for (prop_name, schema) in properties {
+                parts.push(CheckedPart {
+                    name: prop_name.clone(),
+                    access: Access::Property {
+                        name: prop_name.clone(),
+                        optional: !required.contains(prop_name),
+                    },
+                    schema,
+                });
+            }
+        }
+        OpenApiSchema::ArrayTuple {
+            prefix_items,
+            additional_items,
+            ..
+        } => {
+            for field in build_tuple_fields(prefix_items) {
+                if let TupleField::Normal {
+                    name,
+                    schema,
+                    index,
+                } = field
+                {
+                    parts.push(CheckedPart {
+                        name,
+                        access: Access::Item(index),
+                        schema,
+                    });
+                }
+            }
+            if let Some((tail, schema)) = tuple_tail(additional_items.as_deref()) {
+                parts.push(CheckedPart {
+                    name: format!("{tail}[i]"),
+                    access: Access::Tail(prefix_items.len()),
+                    schema,
+                });
+            }
+        }
+        _ => {}
+    }
+    parts
+}
+
+/// The column at which doc comments are wrapped.
+pub const DOC_WIDTH: usize = 80;
+
+/// Writes `description` as a doc comment at the current indentation, with
+/// each line prefixed by `prefix` and wrapped to fit within [`DOC_WIDTH`]
+/// columns where possible.
+///
+/// As in Markdown, blank lines separate paragraphs and other line breaks are
+/// insignificant, except that list items start a new line and that indented
+/// lines and fenced code blocks are kept as they are. `description` must
+/// already be escaped for the comment syntax.
+pub fn write_doc(buf: &mut CodegenBuf, prefix: &str, description: &str) {
+    let width = DOC_WIDTH.saturating_sub(buf.indent_width() + prefix.len());
+    let mut lines: Vec<String> = vec![];
+    // Whether the next line continues the current paragraph, and if so, the
+    // indentation of the paragraph's wrapped lines.
+    let mut paragraph: Option<&str> = None;
+    let mut in_fence = false;
+    for line in description.trim().lines() {
+        let text = line.trim();
+        if text.starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence
+            || text.starts_with("```")
+            || text.is_empty()
+            || line.starts_with(char::is_whitespace)
+        {
+            lines.push(line.trim_end().to_string());
+            paragraph = None;
+            continue;
+        }
+        let list_item = text.starts_with("- ") || text.starts_with("* ");
+        let hang = match paragraph {
+            Some(hang) if !list_item => hang,
+            _ => {
+                lines.push(String::new());
+                if list_item { "  " } else { "" }
+            }
+        };
+        paragraph = Some(hang);
+        for word in text.split_whitespace() {
+            let current = lines.last_mut().unwrap();
+            if current.is_empty() {
+                current.push_str(word);
+            } else if current.len() + 1 + word.len() > width {
+                lines.push(format!("{hang}{word}"));
+            } else {
+                current.push(' ');
+                current.push_str(word);
+            }
+        }
+    }
+    for line in lines {
+        if line.is_empty() {
+            buf.writeln(prefix.trim_end());
+        } else {
+            buf.writeln(format!("{prefix}{line}"));
+        }
+    }
+}
+
 pub fn camel_to_snake_case(input: &str) -> String {
     let mut s = String::new();
     let mut chars = input.chars();
     if let Some(c) = chars.next() {
         s.extend(c.to_lowercase());
     }
-    while let Some(c) = chars.next() {
+    for c in chars {
         if c.is_uppercase() {
             s.push('_');
         }
@@ -344,13 +643,14 @@ pub fn assign_generics(schemas: &mut [OpenApiSchema]) -> Vec<&'static str> {
 
     fn assign(index: &mut usize, schema: &mut OpenApiSchema) {
         match schema {
-            OpenApiSchema::AnyOf { any_of, .. } => {
-                for item in any_of {
+            OpenApiSchema::AnyOf { any_of: arms, .. }
+            | OpenApiSchema::OneOf { one_of: arms, .. } => {
+                for item in arms {
                     assign(index, item);
                 }
             }
             OpenApiSchema::Object { properties, .. } => {
-                for (_, prop_schema) in properties {
+                for prop_schema in properties.values_mut() {
                     assign(index, prop_schema);
                 }
             }
@@ -373,15 +673,24 @@ pub fn assign_generics(schemas: &mut [OpenApiSchema]) -> Vec<&'static str> {
                     assign(index, items);
                 }
             }
-            OpenApiSchema::ArrayTuple { prefix_items, .. } => {
+            OpenApiSchema::ArrayTuple {
+                prefix_items,
+                additional_items,
+                ..
+            } => {
                 for item in prefix_items {
                     assign(index, item);
                 }
+                if let Some(additional_items) = additional_items {
+                    assign(index, additional_items);
+                }
             }
             OpenApiSchema::String { .. }
             | OpenApiSchema::Number { .. }
+            | OpenApiSchema::Integer { .. }
             | OpenApiSchema::Boolean { .. }
             | OpenApiSchema::Const { .. }
+            | OpenApiSchema::Null { .. }
             | OpenApiSchema::Ref { .. }
             | OpenApiSchema::Any { .. } => {}
         }
@@ -1,88 +1,150 @@
 use std::collections::BTreeSet;
 use std::error::Error;
 
-use crate::codegen::{OpenApiSchema, OpenApiSpec, SCHEMA_REF_PREFIX};
+use crate::codegen::{
+    ConstValue, Constraint, IntegerFormat, OpenApiSchema, OpenApiSpec, TargetOptions,
+    diagnostic::{Diagnostics, Pointer},
+    format_bound,
+    shared::{self, Access},
+    strip_schema_ref_prefix,
+};
 use crate::util::codegen_buf::CodegenBuf;
 
-pub fn render(spec: OpenApiSpec) -> Result<CodegenBuf, Box<dyn Error>> {
+const DEFAULT_IMPORT_PATH: &str = "../index";
+
+/// The largest integer that a `number` holds exactly, `Number.MAX_SAFE_INTEGER`.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+pub fn render(
+    spec: OpenApiSpec,
+    options: &TargetOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<CodegenBuf, Box<dyn Error>> {
     let mut buf = CodegenBuf::with_indent("  ");
     buf.writeln("// Code generated by turbopuffer-apigen. DO NOT EDIT.");
     buf.writeln("");
 
+    let import_path = options
+        .import_path
+        .as_deref()
+        .unwrap_or(DEFAULT_IMPORT_PATH);
     for name in spec.unmanaged_schemas {
-        buf.writeln(format!("import {{ {name} }} from '../index';"));
+        buf.writeln(format!("import {{ {name} }} from '{import_path}';"));
     }
     buf.writeln("");
 
-    let managed_names: BTreeSet<String> = spec.managed_schemas.keys().cloned().collect();
+    let ctx = RenderCtx {
+        managed: spec.managed_schemas.keys().cloned().collect(),
+        bigint: options.bigint,
+    };
 
     for (name, schema) in spec.managed_schemas {
-        buf.start_line();
-        buf.write(format!("export type {name}<T = Record<string, any>> = "));
-        render_schema(&mut buf, schema, &managed_names)?;
-        buf.write(";");
-        buf.end_line();
+        let pointer = Pointer::schema(&name);
+        diagnostics.record(&mut buf, &pointer, |buf| {
+            let validated = schema.clone();
+            render_doc(buf, &schema);
+            buf.start_line();
+            buf.write(format!("export type {name}<T = Record<string, any>> = "));
+            render_schema(buf, schema, &ctx, &pointer)?;
+            buf.write(";");
+            buf.end_line();
+            render_validator(buf, &name, &validated, &ctx, &pointer)
+        });
     }
 
     Ok(buf)
 }
 
+struct RenderCtx {
+    /// Names of the managed schemas, which are generic over the document
+    /// type `T`.
+    managed: BTreeSet<String>,
+    /// Whether to represent 64-bit integers as `bigint`.
+    bigint: bool,
+}
+
 fn render_schema(
     buf: &mut CodegenBuf,
     schema: OpenApiSchema,
-    managed: &BTreeSet<String>,
+    ctx: &RenderCtx,
+    pointer: &Pointer,
 ) -> Result<(), Box<dyn Error>> {
     match schema {
         OpenApiSchema::AnyOf {
-            _description: _,
+            description: _,
             any_of,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_union(buf, any_of, ctx, &pointer.join("anyOf"))?,
+        // The variants' discriminator properties are string literal types,
+        // which makes this a discriminated union.
+        OpenApiSchema::OneOf {
+            description: _,
+            one_of,
+            discriminator: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => render_union(buf, one_of, ctx, &pointer.join("oneOf"))?,
+        OpenApiSchema::Object {
+            description: _,
+            _type: _,
+            properties,
+            required,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
-            let expanded = any_of.len() > 3;
+            // Documented properties get a line each, for their doc comments.
+            let expanded = properties.values().any(|schema| {
+                schema.description().is_some()
+                    || schema.default().is_some()
+                    || schema.deprecated().is_some()
+            });
+            buf.write("{");
             if expanded {
-                buf.unwrite_one(); // remove the trailing space from the last line
+                buf.end_line();
                 buf.indent();
+            } else {
+                buf.write(" ");
             }
-            for (i, schema) in any_of.into_iter().enumerate() {
+            for (i, (name, schema)) in properties.into_iter().enumerate() {
                 if expanded {
-                    buf.end_line();
+                    render_doc(buf, &schema);
                     buf.start_line();
-                    buf.write("| ");
                 } else if i > 0 {
-                    buf.write(" | ");
+                    buf.write(", ");
+                }
+                if required.contains(&name) {
+                    buf.write(format!("{name}: "));
+                } else {
+                    buf.write(format!("{name}?: "));
+                }
+                let pointer = pointer.join("properties").join(&name);
+                render_schema(buf, schema, ctx, &pointer)?;
+                if expanded {
+                    buf.write(";");
+                    buf.end_line();
                 }
-                render_schema(buf, schema, managed)?;
             }
             if expanded {
                 buf.unindent();
+                buf.start_line();
+                buf.write("}");
+            } else {
+                buf.write(" }");
             }
         }
-        OpenApiSchema::Object {
-            _description: _,
-            _type: _,
-            properties,
-            required,
-            title: _,
-        } => {
-            if !properties.keys().all(|name| required.contains(name)) {
-                Err("object schemas with non-required properties not supported")?
-            };
-            buf.write("{ ");
-            for (i, (name, schema)) in properties.into_iter().enumerate() {
-                if i > 0 {
-                    buf.write(", ");
-                }
-                buf.write(format!("{name}: "));
-                render_schema(buf, schema, managed)?;
-            }
-            buf.write(" }")
-        }
         OpenApiSchema::Map {
-            _description: _,
+            description: _,
             _type: _,
             additional_properties,
             property_names,
             x_turbopuffer_variant_name: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             let key_type =
                 if property_names.as_deref().and_then(OpenApiSchema::title) == Some("attr") {
@@ -91,7 +153,8 @@ fn render_schema(
                     "string"
                 };
             buf.write(format!("Record<{key_type}, "));
-            render_schema(buf, *additional_properties, managed)?;
+            let pointer = pointer.join("additionalProperties");
+            render_schema(buf, *additional_properties, ctx, &pointer)?;
             buf.write(">")
         }
         OpenApiSchema::ArrayList {
@@ -99,37 +162,55 @@ fn render_schema(
             _type: _,
             items,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            min_items: _,
+            max_items: _,
         } => {
-            render_schema(buf, *items, managed)?;
+            render_schema(buf, *items, ctx, &pointer.join("items"))?;
             buf.write("[]");
         }
         OpenApiSchema::ArrayTuple {
-            additional_items: true,
-            ..
-        } => Err("tuple-type arrays with `items: true` unsupported")?,
-        OpenApiSchema::ArrayTuple {
-            _description: _,
+            description: _,
             _type: _,
-            additional_items: false,
+            additional_items,
             prefix_items,
             x_turbopuffer_variant_name: _,
             x_turbopuffer_variant_drop_on_conflict: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
         } => {
             buf.write("[");
+            let prefix_len = prefix_items.len();
             for (i, schema) in prefix_items.into_iter().enumerate() {
                 if i > 0 {
                     buf.write(", ");
                 }
-                render_schema(buf, schema, managed)?;
+                render_schema(buf, schema, ctx, &pointer.join("prefixItems").join(i))?;
+            }
+            if let Some(additional_items) = additional_items {
+                if prefix_len > 0 {
+                    buf.write(", ");
+                }
+                buf.write("...");
+                let pointer = pointer.join("additionalItems");
+                render_schema(buf, *additional_items, ctx, &pointer)?;
+                buf.write("[]");
             }
             buf.write("]")
         }
         OpenApiSchema::String {
-            _description: _,
+            description: _,
             _type: _,
             x_turbopuffer_variant_name: _,
             title,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            min_length: _,
+            max_length: _,
+            pattern: _,
         } => {
             if title.as_deref() == Some("attr") {
                 buf.write("keyof T & string");
@@ -138,31 +219,376 @@ fn render_schema(
             }
         }
         OpenApiSchema::Number {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
             x_turbopuffer_width: _,
         } => buf.write("number"),
+        OpenApiSchema::Integer {
+            description: _,
+            _type: _,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
+            minimum: _,
+            exclusive_minimum: _,
+            maximum: _,
+            exclusive_maximum: _,
+            format,
+        } => match IntegerFormat::parse(format.as_deref(), pointer)? {
+            IntegerFormat::Int64 | IntegerFormat::Uint64 if ctx.bigint => buf.write("bigint"),
+            _ => buf.write("number"),
+        },
         OpenApiSchema::Boolean {
-            _description: _,
+            description: _,
             _type: _,
             title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+            default: _,
         } => buf.write("boolean"),
         OpenApiSchema::Const {
-            _description: _,
-            sconst,
+            description: _,
+            value,
             title: _,
-        } => buf.write(format!("'{sconst}'")),
-        OpenApiSchema::Ref { sref, title: _ } => match sref.strip_prefix(SCHEMA_REF_PREFIX) {
-            None => Err(format!("unsupported reference: {sref}"))?,
-            Some(name) => {
-                buf.write(name);
-                if managed.contains(name) {
-                    buf.write("<T>");
-                }
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => match value {
+            ConstValue::String(sconst) => buf.write(format!("'{}'", escape_string(&sconst))),
+            // A `number` can't hold larger integers exactly, but a `bigint`
+            // can, and matches the type of 64-bit integers then too.
+            ConstValue::Integer(n) if ctx.bigint && n.unsigned_abs() > MAX_SAFE_INTEGER => {
+                buf.write(format!("{n}n"))
+            }
+            ConstValue::UInteger(n) if ctx.bigint && n > MAX_SAFE_INTEGER => {
+                buf.write(format!("{n}n"))
             }
+            // JSON literals are valid TypeScript literal types otherwise.
+            _ => buf.write(value.to_string()),
         },
+        OpenApiSchema::Ref {
+            sref,
+            title: _,
+            deprecated: _,
+            x_turbopuffer_deprecated_message: _,
+        } => {
+            let name = strip_schema_ref_prefix(&sref, pointer)?;
+            buf.write(name);
+            if ctx.managed.contains(name) {
+                buf.write("<T>");
+            }
+        }
+        OpenApiSchema::Null { .. } => buf.write("null"),
         OpenApiSchema::Any { .. } => buf.write("any"),
     }
     Ok(())
 }
+
+/// Writes `validate<name>`, a function that checks a value of the schema
+/// `name` against the constraints on it and its parts, throwing an `Error` for
+/// the first it violates, and otherwise returns the value. Writes nothing if
+/// there are no constraints.
+///
+/// Patterns are compiled once, into constants next to the function.
+fn render_validator(
+    buf: &mut CodegenBuf,
+    name: &str,
+    schema: &OpenApiSchema,
+    ctx: &RenderCtx,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let parts = shared::checked_parts(name, schema);
+    if parts
+        .iter()
+        .all(|part| part.schema.constraints().is_empty())
+    {
+        return Ok(());
+    }
+    let pattern_var = |part: &str| {
+        let part = part.trim_end_matches("[i]");
+        shared::lower_camel_case(&shared::snake_to_camel_case(&format!(
+            "{name}_{part}_pattern"
+        )))
+    };
+    for part in &parts {
+        for constraint in part.schema.constraints() {
+            if let Constraint::Pattern(pattern) = constraint {
+                let var = match part.access {
+                    Access::Value => pattern_var(""),
+                    _ => pattern_var(&part.name),
+                };
+                let pattern = escape_string(pattern);
+                buf.writeln(format!("const {var} = new RegExp('{pattern}');"));
+            }
+        }
+    }
+    buf.writeln(format!(
+        "export function validate{name}<T = Record<string, any>>(value: {name}<T>): {name}<T> {{"
+    ));
+    buf.indent();
+    for part in &parts {
+        let constraints = part.schema.constraints();
+        if constraints.is_empty() {
+            continue;
+        }
+        let (mut guard, value) = match &part.access {
+            Access::Value => (String::new(), "value".to_string()),
+            Access::Property { name, optional } => {
+                let value = format!("value.{name}");
+                let guard = match optional {
+                    true => format!("{value} !== undefined && "),
+                    false => String::new(),
+                };
+                (guard, value)
+            }
+            Access::Item(i) => (String::new(), format!("value[{i}]")),
+            Access::Tail(start) => {
+                buf.writeln(format!("for (const item of value.slice({start})) {{"));
+                buf.indent();
+                (String::new(), "item".to_string())
+            }
+        };
+        // Nullable parts are only checked when set.
+        let inner = part.schema.without_null();
+        if inner.is_some() {
+            guard.push_str(&format!("{value} !== null && "));
+        }
+        let schema = inner.as_ref().unwrap_or(part.schema);
+        // The bounds of 64-bit integers are `bigint`s too when their values
+        // are.
+        let suffix = match schema {
+            OpenApiSchema::Integer { format, .. } if ctx.bigint => {
+                match IntegerFormat::parse(format.as_deref(), pointer)? {
+                    IntegerFormat::Int64 | IntegerFormat::Uint64 => "n",
+                    _ => "",
+                }
+            }
+            _ => "",
+        };
+        for constraint in constraints {
+            let violated = match constraint {
+                Constraint::MinItems(n) => format!("{value}.length < {n}"),
+                Constraint::MaxItems(n) => format!("{value}.length > {n}"),
+                // Lengths are in code points, not UTF-16 code units.
+                Constraint::MinLength(n) => format!("[...{value}].length < {n}"),
+                Constraint::MaxLength(n) => format!("[...{value}].length > {n}"),
+                Constraint::Minimum(n) => format!("{value} < {}{suffix}", format_bound(n)),
+                Constraint::ExclusiveMinimum(n) => {
+                    format!("{value} <= {}{suffix}", format_bound(n))
+                }
+                Constraint::Maximum(n) => format!("{value} > {}{suffix}", format_bound(n)),
+                Constraint::ExclusiveMaximum(n) => {
+                    format!("{value} >= {}{suffix}", format_bound(n))
+                }
+                Constraint::Pattern(_) => {
+                    let var = match part.access {
+                        Access::Value => pattern_var(""),
+                        _ => pattern_var(&part.name),
+                    };
+                    format!("!{var}.test({value})")
+                }
+            };
+            buf.writeln(format!("if ({guard}{violated}) {{"));
+            buf.indent();
+            let message = escape_string(&constraint.message(&part.name));
+            buf.writeln(format!("throw new Error('{message}');"));
+            buf.unindent();
+            buf.writeln("}");
+        }
+        if let Access::Tail(_) = part.access {
+            buf.unindent();
+            buf.writeln("}");
+        }
+    }
+    buf.writeln("return value;");
+    buf.unindent();
+    buf.writeln("}");
+    Ok(())
+}
+
+/// Writes the description of `schema`, if any, its default, if it has one,
+/// and its deprecation notice, if it's deprecated, as a JSDoc comment.
+fn render_doc(buf: &mut CodegenBuf, schema: &OpenApiSchema) {
+    let default = schema
+        .default()
+        .map(|value| format!("@default {}", escape_doc(&value.to_string())));
+    let deprecated = schema
+        .deprecated()
+        .map(|message| format!("@deprecated {}", escape_doc(message)));
+    let paragraphs: Vec<_> = [
+        schema.description().map(|d| escape_doc(d.trim())),
+        default,
+        deprecated,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if paragraphs.is_empty() {
+        return;
+    }
+    let description = paragraphs.join("\n\n");
+    if !description.contains('\n')
+        && buf.indent_width() + description.len() + 7 <= shared::DOC_WIDTH
+    {
+        buf.writeln(format!("/** {description} */"));
+    } else {
+        buf.writeln("/**");
+        shared::write_doc(buf, " * ", &description);
+        buf.writeln(" */");
+    }
+}
+
+/// Escapes `description` for inclusion in a JSDoc comment.
+fn escape_doc(description: &str) -> String {
+    description.replace("*/", "*\\/")
+}
+
+/// Escapes `s` for inclusion in a single-quoted TypeScript string literal.
+fn escape_string(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// Renders the `arms` of an `anyOf` or `oneOf` at `pointer` as a union type.
+fn render_union(
+    buf: &mut CodegenBuf,
+    arms: Vec<OpenApiSchema>,
+    ctx: &RenderCtx,
+    pointer: &Pointer,
+) -> Result<(), Box<dyn Error>> {
+    let expanded = arms.len() > 3;
+    if expanded {
+        buf.unwrite_one(); // remove the trailing space from the last line
+        buf.indent();
+    }
+    for (i, schema) in arms.into_iter().enumerate() {
+        if expanded {
+            buf.end_line();
+            buf.start_line();
+            buf.write("| ");
+        } else if i > 0 {
+            buf.write(" | ");
+        }
+        render_schema(buf, schema, ctx, &pointer.join(i))?;
+    }
+    if expanded {
+        buf.unindent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::tests::{CONSTS, UINT64, render_schemas, render_schemas_with};
+
+    #[test]
+    fn consts() {
+        let out = render_schemas(render, CONSTS).unwrap();
+        assert!(
+            out.contains("[keyof T & string, 18446744073709551615, -3]"),
+            "{out}"
+        );
+        assert!(out.contains(r#"{ op: 'a\nb\t$c"\r' }"#), "{out}");
+
+        let options = TargetOptions {
+            bigint: true,
+            ..TargetOptions::default()
+        };
+        let out = render_schemas_with(render, &options, CONSTS).unwrap();
+        assert!(
+            out.contains("[keyof T & string, 18446744073709551615n, -3]"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn uint64_bounds() {
+        let out = render_schemas(render, UINT64).unwrap();
+        assert!(
+            out.ends_with(
+                "
+export type FilterCount<T = Record<string, any>> = [keyof T & string, 'Count', number];
+export function validateFilterCount<T = Record<string, any>>(value: FilterCount<T>): FilterCount<T> {
+  if (value[2] < 1) {
+    throw new Error('n must be at least 1');
+  }
+  if (value[2] > 100) {
+    throw new Error('n must be at most 100');
+  }
+  return value;
+}
+"
+            ),
+            "{out}"
+        );
+
+        let options = TargetOptions {
+            bigint: true,
+            ..TargetOptions::default()
+        };
+        let out = render_schemas_with(render, &options, UINT64).unwrap();
+        assert!(out.contains("[keyof T & string, 'Count', bigint]"), "{out}");
+        assert!(out.contains("  if (value[2] < 1n) {\n"), "{out}");
+        assert!(out.contains("  if (value[2] > 100n) {\n"), "{out}");
+    }
+
+    #[test]
+    fn constraints_are_checked() {
+        let out = render_schemas(
+            render,
+            r#"
+Page:
+  type: object
+  properties:
+    cursor: {type: string, pattern: '^\d+$'}
+    note: {anyOf: [{type: string, minLength: 2}, {type: 'null'}]}
+  required: [cursor]
+Ranked:
+  type: array
+  prefixItems:
+    - {type: string, title: label}
+  items: {type: string, title: tag, maxLength: 8}
+"#,
+        )
+        .unwrap();
+        assert!(
+            out.contains(
+                r#"
+const pageCursorPattern = new RegExp('^\\d+$');
+export function validatePage<T = Record<string, any>>(value: Page<T>): Page<T> {
+  if (!pageCursorPattern.test(value.cursor)) {
+    throw new Error('cursor must match ^\\d+$');
+  }
+  if (value.note !== undefined && value.note !== null && [...value.note].length < 2) {
+    throw new Error('note must be at least 2 characters long');
+  }
+"#
+            ),
+            "{out}"
+        );
+        assert!(
+            out.contains(
+                r#"
+  for (const item of value.slice(1)) {
+    if ([...item].length > 8) {
+      throw new Error('tag[i] must be at most 8 characters long');
+    }
+  }
+"#
+            ),
+            "{out}"
+        );
+    }
+}
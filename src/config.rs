@@ -0,0 +1,169 @@
+//! Configuration file support.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{Language, codegen::TargetOptions};
+
+/// The default path of the configuration file, relative to the working
+/// directory.
+pub const DEFAULT_CONFIG_PATH: &str = "apigen.yml";
+
+/// The contents of an `apigen.yml` configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Which schemas to generate code for.
+    #[serde(default)]
+    pub managed: ManagedConfig,
+    /// The targets to generate code for.
+    pub targets: Vec<TargetConfig>,
+}
+
+/// Configures which schemas are managed by the generator.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManagedConfig {
+    /// Schemas whose names start with any of these prefixes are managed. If
+    /// omitted, a default set of prefixes covering the turbolisp types is
+    /// used.
+    pub prefixes: Option<Vec<String>>,
+    /// Schemas with exactly these names are managed.
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Schemas whose names match any of these globs are never managed.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// If non-empty, only these schemas and the otherwise-selected schemas
+    /// reachable from them are managed.
+    #[serde(default)]
+    pub roots: Vec<String>,
+}
+
+/// A single code generation target.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+    /// The language to generate code for.
+    pub language: Language,
+    /// The file to write the generated code to. Relative paths are resolved
+    /// against the directory containing the configuration file.
+    pub output: PathBuf,
+    /// Language-specific options for the target.
+    #[serde(default)]
+    pub options: TargetOptions,
+}
+
+impl Config {
+    /// Loads the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("reading config file {}: {e}", path.display()))?;
+        Config::parse(&content, path)
+    }
+
+    /// Parses the contents of the configuration file at `path`.
+    fn parse(content: &str, path: &Path) -> Result<Config, Box<dyn Error>> {
+        let mut config: Config = serde_yaml::from_str(content)
+            .map_err(|e| format!("parsing config file {}: {e}", path.display()))?;
+        if config.targets.is_empty() {
+            Err(format!("config file {} defines no targets", path.display()))?
+        }
+        let base = path.parent().unwrap_or(Path::new(""));
+        for target in &mut config.targets {
+            target.output = base.join(&target.output);
+            target
+                .options
+                .validate(target.language)
+                .map_err(|e| format!("{}: {e}", target.output.display()))?;
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Config, String> {
+        Config::parse(content, Path::new("sdk/apigen.yml")).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn resolves_outputs_against_the_config_directory() {
+        let config = parse(
+            "
+targets:
+  - language: go
+    output: go/types.go
+    options:
+      package: tpuf
+  - language: python
+    output: /abs/types.py
+",
+        )
+        .unwrap();
+        assert_eq!(config.targets.len(), 2);
+        assert_eq!(config.targets[0].language, Language::Go);
+        assert_eq!(config.targets[0].output, Path::new("sdk/go/types.go"));
+        assert_eq!(config.targets[0].options.package.as_deref(), Some("tpuf"));
+        assert_eq!(config.targets[1].output, Path::new("/abs/types.py"));
+        assert!(config.managed.prefixes.is_none());
+    }
+
+    #[test]
+    fn parses_managed_selection() {
+        let config = parse(
+            "
+managed:
+  prefixes: [Filter]
+  names: [Query]
+  exclude: ['*Internal']
+  roots: [Query]
+targets:
+  - language: typescript
+    output: types.ts
+",
+        )
+        .unwrap();
+        assert_eq!(config.managed.prefixes, Some(vec!["Filter".to_string()]));
+        assert_eq!(config.managed.names, ["Query"]);
+        assert_eq!(config.managed.exclude, ["*Internal"]);
+        assert_eq!(config.managed.roots, ["Query"]);
+    }
+
+    #[test]
+    fn rejects_empty_targets() {
+        let err = parse("targets: []").unwrap_err();
+        assert_eq!(err, "config file sdk/apigen.yml defines no targets");
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err = parse("targets: []\nextra: 1").unwrap_err();
+        assert!(err.contains("unknown field `extra`"), "{err}");
+    }
+
+    #[test]
+    fn rejects_options_for_other_languages() {
+        let err = parse(
+            "
+targets:
+  - language: python
+    output: types.py
+    options:
+      package: tpuf
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "sdk/types.py: option package does not apply to python targets"
+        );
+    }
+}
@@ -1,13 +1,21 @@
-use std::{error::Error, fmt, fs, process};
+use std::{error::Error, fmt, fs, io, path::PathBuf, process};
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
+use similar::TextDiff;
 
-use crate::codegen::OpenApiSpec;
+use crate::codegen::diagnostic::{self, Diagnostics, DiagnosticsFormat, Pointer, SpecFile};
+use crate::codegen::{OpenApiSpec, SchemaSelector, TargetOptions};
+use crate::config::{Config, ManagedConfig};
+use crate::spec::{SpecCache, SpecSource, StainlessStats};
 
 mod codegen;
+mod config;
+mod spec;
 mod util;
 
+/// The default prefixes of managed schema names, used unless overridden on the
+/// command line or in the config file.
 const TYPE_PREFIXES: &[&str] = &[
     "Aggregate",
     "Expr",
@@ -18,18 +26,115 @@ const TYPE_PREFIXES: &[&str] = &[
 ];
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The OpenAPI specification to generate code from, as a local path, an
+    /// HTTP(S) URL, or `-` for stdin. The specification may be in either YAML
+    /// or JSON format.
+    ///
+    /// Defaults to the `openapi_spec_url` in the Stainless `.stats.yml` file
+    /// in the working directory.
+    #[arg(long, global = true, env = "SPEC_FILE_PATH")]
+    spec: Option<SpecSource>,
+    /// Generate code even if the OpenAPI specification downloaded from the
+    /// URL in `.stats.yml` does not match the `openapi_spec_hash` recorded
+    /// alongside it.
+    #[arg(long, global = true)]
+    allow_hash_mismatch: bool,
+    /// The directory in which to cache downloaded OpenAPI specifications.
+    ///
+    /// Defaults to `turbopuffer-apigen` inside the user's cache directory.
+    #[arg(long, global = true, env = "APIGEN_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Never access the network. The OpenAPI specification must be a local
+    /// file or already present in the cache.
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Manage schemas whose names start with this prefix. May be repeated.
+    ///
+    /// Replaces the default set of prefixes (and any prefixes in the config
+    /// file).
+    #[arg(long, global = true)]
+    managed_prefix: Vec<String>,
+    /// Manage the schema with this exact name. May be repeated.
+    #[arg(long, global = true)]
+    managed_name: Vec<String>,
+    /// Never manage schemas whose names match this glob, even if otherwise
+    /// selected. May be repeated.
+    #[arg(long, global = true)]
+    managed_exclude: Vec<String>,
+    /// Manage only this schema and the otherwise-selected schemas reachable
+    /// from it. May be repeated.
+    #[arg(long, global = true)]
+    root: Vec<String>,
+    /// The format in which to report errors.
+    ///
+    /// Errors in the OpenAPI specification carry a stable code and the JSON
+    /// pointer of the offending schema. In the `json` and `sarif` formats a
+    /// report is written even when there are no errors.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    diagnostics_format: DiagnosticsFormat,
+    /// Write the error report to this file rather than to stderr.
+    #[arg(long, global = true)]
+    diagnostics_output: Option<PathBuf>,
     /// The language to generate code for.
-    #[arg(value_enum)]
+    #[arg(value_enum, required = true)]
+    language: Option<Language>,
+    /// Write the generated code to this file rather than to stdout.
+    ///
+    /// The file is replaced atomically, so a failed run never leaves behind a
+    /// partially written file.
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Rather than writing the generated code, check that the file at
+    /// `--output` is up to date. If it is not, prints a diff and exits with a
+    /// non-zero status.
+    #[arg(long, requires = "output")]
+    check: bool,
+    #[command(flatten)]
+    options: TargetOptions,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate code for every target listed in a configuration file.
+    Generate {
+        /// The configuration file to read.
+        #[arg(long, default_value = config::DEFAULT_CONFIG_PATH)]
+        config: PathBuf,
+        /// Rather than writing the generated code, check that each target's
+        /// output file is up to date. If any is not, prints a diff and exits
+        /// with a non-zero status.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Write a copy of the OpenAPI specification that Stainless can read, with
+    /// the `items` of tuples spelled `additionalItems`.
+    StainlessSpec {
+        /// Write the specification to this file rather than to stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// A single unit of work: a language to generate code for and where to put
+/// the result.
+struct Target {
     language: Language,
+    output: Option<PathBuf>,
+    options: TargetOptions,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Language {
     Python,
     Go,
     Typescript,
     Java,
+    #[serde(alias = "csharp")]
     CSharp,
 }
 
@@ -47,55 +152,290 @@ impl fmt::Display for Language {
 
 fn main() {
     let args = Args::parse();
-    if let Err(e) = run(args.language) {
-        eprint!("error: {e}");
-        let mut e = &*e;
-        while let Some(source) = e.source() {
-            eprint!(": {source}");
-            e = source;
+    // The location and content of the OpenAPI spec, once loaded, so that
+    // errors can be located within it.
+    let mut spec = None;
+    let result = run(&args, &mut spec);
+    let diagnostics = match result {
+        Ok(()) => Diagnostics::default(),
+        Err(e) => Diagnostics::from_error(e),
+    };
+    // Diagnostics in schemas imported from other files point into those
+    // files, so load them too.
+    let mut documents: Vec<(String, String)> = vec![];
+    for d in diagnostics.as_slice() {
+        let Some(uri) = d.pointer.as_ref().and_then(Pointer::file) else {
+            continue;
+        };
+        if documents.iter().any(|(loaded, _)| loaded == uri) {
+            continue;
+        }
+        if let Ok(content) = fs::read_to_string(uri) {
+            documents.push((uri.to_string(), content));
         }
-        eprintln!();
+    }
+    let files: Vec<_> = (spec.iter().chain(&documents))
+        .map(|(uri, content)| SpecFile { uri, content })
+        .collect();
+    let report = match (args.diagnostics_format, diagnostics.is_empty()) {
+        (DiagnosticsFormat::Text, true) => None,
+        (format, _) => Some(diagnostic::render(diagnostics.as_slice(), format, &files)),
+    };
+    if let Some(report) = report {
+        match &args.diagnostics_output {
+            Some(path) => {
+                if let Err(e) = util::fs::write_atomic(path, &report) {
+                    eprintln!("error: writing {}: {e}", path.display());
+                    process::exit(1);
+                }
+            }
+            None => eprint!("{report}"),
+        }
+    }
+    if !diagnostics.is_empty() {
         process::exit(1);
     }
 }
 
-pub fn run(language: Language) -> Result<(), Box<dyn Error>> {
-    log!("generating code for {}", language);
-
-    log!("reading Stainless stats file");
-    let stats_content = fs::read_to_string(".stats.yml")?;
-    let stainless_stats: StainlessStats = serde_yaml::from_str(&stats_content)?;
-
-    let openapi_yaml = if let Ok(spec_file_path) = std::env::var("SPEC_FILE_PATH") {
-        log!("reading OpenAPI spec from local file: {}", spec_file_path);
-        fs::read_to_string(&spec_file_path)?
-    } else {
-        log!(
-            "discovered OpenAPI spec url: {}",
-            stainless_stats.openapi_spec_url
-        );
-        log!("downloading OpenAPI spec");
-        let resp = reqwest::blocking::get(&stainless_stats.openapi_spec_url)?;
-        resp.text()?
+fn run(args: &Args, spec: &mut Option<(String, String)>) -> Result<(), Box<dyn Error>> {
+    let (targets, check, managed) = match &args.command {
+        Some(Command::Generate { config, check }) => {
+            log!("reading config file {}", config.display());
+            let config = Config::load(config)?;
+            let targets = config
+                .targets
+                .into_iter()
+                .map(|t| Target {
+                    language: t.language,
+                    output: Some(t.output),
+                    options: t.options,
+                })
+                .collect();
+            (targets, *check, config.managed)
+        }
+        Some(Command::StainlessSpec { output }) => {
+            let (source, content) = load_spec(args)?;
+            let (_, content) = spec.insert((source.to_string(), content));
+            log!("rewriting OpenAPI spec for Stainless");
+            let content = codegen::stainless_spec(content)?;
+            match output {
+                Some(output) => {
+                    log!("writing OpenAPI spec to {}", output.display());
+                    util::fs::write_atomic(output, &content)?;
+                }
+                None => print!("{content}"),
+            }
+            return Ok(());
+        }
+        None => {
+            let language = args.language.expect("required by clap");
+            args.options.validate(language)?;
+            let target = Target {
+                language,
+                output: args.output.clone(),
+                options: args.options.clone(),
+            };
+            (vec![target], args.check, ManagedConfig::default())
+        }
     };
 
+    let selector = SchemaSelector {
+        prefixes: if !args.managed_prefix.is_empty() {
+            args.managed_prefix.clone()
+        } else if let Some(prefixes) = managed.prefixes {
+            prefixes
+        } else {
+            TYPE_PREFIXES.iter().map(|p| p.to_string()).collect()
+        },
+        names: managed
+            .names
+            .into_iter()
+            .chain(args.managed_name.iter().cloned())
+            .collect(),
+        exclude: managed
+            .exclude
+            .into_iter()
+            .chain(args.managed_exclude.iter().cloned())
+            .collect(),
+        roots: managed
+            .roots
+            .into_iter()
+            .chain(args.root.iter().cloned())
+            .collect(),
+    };
+
+    let (source, content) = load_spec(args)?;
+    let (_, openapi_content) = spec.insert((source.to_string(), content));
+
     log!("parsing OpenAPI spec");
-    let openapi_spec = OpenApiSpec::parse(&openapi_yaml, TYPE_PREFIXES)?;
-
-    let content = match language {
-        Language::Go => codegen::go::render(openapi_spec)?,
-        Language::Java => codegen::java::render(openapi_spec)?,
-        Language::Python => codegen::python::render(openapi_spec)?,
-        Language::Typescript => codegen::typescript::render(openapi_spec)?,
-        Language::CSharp => codegen::csharp::render(openapi_spec)?,
+    let path = match &source {
+        SpecSource::File(path) => Some(path.as_path()),
+        SpecSource::Stdin | SpecSource::Url(_) => None,
     };
+    let openapi_spec = OpenApiSpec::parse(openapi_content, path, &selector)?;
+
+    // Render every target before touching the filesystem, so that a failure
+    // in one target doesn't leave only some of the targets regenerated.
+    // Rendering continues past failures so that every problem is reported at
+    // once.
+    let mut rendered = vec![];
+    let mut diagnostics = Diagnostics::default();
+    for target in targets {
+        let language = target.language;
+        log!("generating code for {language}");
+        let spec = openapi_spec.clone();
+        let mut target_diagnostics = Diagnostics::default();
+        let d = &mut target_diagnostics;
+        let result = match language {
+            Language::Go => codegen::go::render(spec, &target.options, d),
+            Language::Java => codegen::java::render(spec, &target.options, d),
+            Language::Python => codegen::python::render(spec, &target.options, d),
+            Language::Typescript => codegen::typescript::render(spec, &target.options, d),
+            Language::CSharp => codegen::csharp::render(spec, &target.options, d),
+        };
+        match result {
+            Ok(content) => rendered.push((target, content.into_string())),
+            Err(e) => target_diagnostics.extend(Diagnostics::from_error(e)),
+        }
+        let target_diagnostics = target_diagnostics.relocate(&openapi_spec.imports);
+        diagnostics.extend(target_diagnostics.or_in(language));
+    }
+
+    if !diagnostics.is_empty() {
+        // Print whatever could be rendered for inspection, but never write a
+        // partial result to disk.
+        for (target, content) in rendered {
+            match target.output {
+                Some(output) => log!("not writing {} due to errors", output.display()),
+                None => print!("{content}"),
+            }
+        }
+        return Err(diagnostics.into());
+    }
 
-    print!("{}", content.into_string());
+    let mut stale = vec![];
+    for (target, content) in rendered {
+        match (target.output, check) {
+            (Some(output), true) => {
+                log!("checking generated code in {}", output.display());
+                let existing = match fs::read_to_string(&output) {
+                    Ok(existing) => existing,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                if existing != content {
+                    let path = output.display().to_string();
+                    let diff = TextDiff::from_lines(&existing, &content);
+                    print!("{}", diff.unified_diff().header(&path, &path));
+                    stale.push(path);
+                }
+            }
+            (Some(output), false) => {
+                log!("writing generated code to {}", output.display());
+                util::fs::write_atomic(&output, &content)?;
+            }
+            (None, _) => print!("{content}"),
+        }
+    }
+    if !stale.is_empty() {
+        Err(format!(
+            "generated code is out of date: {}",
+            stale.join(", ")
+        ))?
+    }
 
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct StainlessStats {
-    openapi_spec_url: String,
+/// Fetches the OpenAPI specification selected by `args`, consulting the spec
+/// cache where possible and verifying the spec's hash if one is known.
+///
+/// Returns the spec's source along with its content.
+fn load_spec(args: &Args) -> Result<(SpecSource, String), Box<dyn Error>> {
+    let (source, expected_hash) = match &args.spec {
+        Some(source) => (source.clone(), None),
+        None => {
+            log!("reading Stainless stats file");
+            let stainless_stats = StainlessStats::load()?;
+            log!(
+                "discovered OpenAPI spec url: {}",
+                stainless_stats.openapi_spec_url
+            );
+            if stainless_stats.openapi_spec_hash.is_none() {
+                log!("warning: .stats.yml records no openapi_spec_hash; skipping verification");
+            }
+            (
+                SpecSource::Url(stainless_stats.openapi_spec_url),
+                stainless_stats.openapi_spec_hash,
+            )
+        }
+    };
+
+    let SpecSource::Url(url) = &source else {
+        log!("reading OpenAPI spec from {source}");
+        let content = source.read()?;
+        return Ok((source, content));
+    };
+
+    let cache = args
+        .cache_dir
+        .clone()
+        .or_else(SpecCache::default_dir)
+        .map(SpecCache::new);
+    let key = SpecCache::key(url, expected_hash.as_deref());
+
+    // Entries keyed by hash are immutable, so they can be used even when
+    // online. Entries keyed by URL may be stale, so they are used only when
+    // offline.
+    let cached = match &cache {
+        Some(cache) if args.offline || expected_hash.is_some() => {
+            match cache.get(&key, expected_hash.as_deref()) {
+                // Online, a corrupt entry is replaced by downloading the spec
+                // again.
+                Err(e) if !args.offline => {
+                    log!("warning: ignoring corrupt cache entry: {e}");
+                    None
+                }
+                cached => cached?,
+            }
+        }
+        _ => None,
+    };
+    if let Some(content) = cached {
+        log!("using cached OpenAPI spec");
+        match &expected_hash {
+            Some(hash) => match spec::verify_hash(&content, hash) {
+                Ok(()) => return Ok((source, content)),
+                // Offline, a corrupt entry can't be replaced, and isn't
+                // missing either.
+                Err(e) if args.offline => {
+                    Err(format!("spec cache entry for {url} is corrupt: {e}"))?
+                }
+                Err(e) => log!("warning: ignoring corrupt cache entry: {e}"),
+            },
+            None => return Ok((source, content)),
+        }
+    }
+
+    if args.offline {
+        match &cache {
+            Some(cache) => Err(format!(
+                "OpenAPI spec {url} is not in the cache at {}; rerun without --offline to populate it",
+                cache.dir().display()
+            ))?,
+            None => Err("--offline requires a cache directory; pass --cache-dir")?,
+        }
+    }
+
+    log!("downloading OpenAPI spec from {url}");
+    let content = source.read()?;
+
+    let verified =
+        spec::verify_download(&content, expected_hash.as_deref(), args.allow_hash_mismatch)?;
+    // Never store a spec under a hash it doesn't match.
+    if let (Some(cache), true) = (&cache, verified) {
+        cache.put(&key, &content)?;
+    }
+
+    Ok((source, content))
 }
@@ -0,0 +1,321 @@
+//! Locating and fetching the OpenAPI specification.
+
+use std::{
+    convert::Infallible,
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, Read},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::util;
+
+/// The path of the Stainless stats file, relative to the working directory.
+const STAINLESS_STATS_PATH: &str = ".stats.yml";
+
+/// Where to read the OpenAPI specification from.
+#[derive(Debug, Clone)]
+pub enum SpecSource {
+    /// Read the specification from stdin.
+    Stdin,
+    /// Download the specification from a URL.
+    Url(String),
+    /// Read the specification from a local file.
+    File(PathBuf),
+}
+
+impl SpecSource {
+    /// Fetches the contents of the specification.
+    pub fn read(&self) -> Result<String, Box<dyn Error>> {
+        match self {
+            SpecSource::Stdin => {
+                let mut content = String::new();
+                io::stdin().read_to_string(&mut content)?;
+                Ok(content)
+            }
+            SpecSource::Url(url) => {
+                let resp = reqwest::blocking::get(url)?.error_for_status()?;
+                Ok(resp.text()?)
+            }
+            SpecSource::File(path) => fs::read_to_string(path)
+                .map_err(|e| format!("reading {}: {e}", path.display()).into()),
+        }
+    }
+}
+
+impl FromStr for SpecSource {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(if s == "-" {
+            SpecSource::Stdin
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            SpecSource::Url(s.into())
+        } else {
+            SpecSource::File(s.into())
+        })
+    }
+}
+
+impl fmt::Display for SpecSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecSource::Stdin => write!(f, "stdin"),
+            SpecSource::Url(url) => write!(f, "{url}"),
+            SpecSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A local, content-addressed cache of downloaded specifications.
+///
+/// Entries are keyed by the spec hash recorded in `.stats.yml` when one is
+/// available, and by the digest of the spec URL otherwise. Each entry is
+/// stored with the SHA-256 digest of its content, which is checked whenever
+/// the entry is read.
+#[derive(Debug)]
+pub struct SpecCache {
+    dir: PathBuf,
+}
+
+impl SpecCache {
+    pub fn new(dir: PathBuf) -> SpecCache {
+        SpecCache { dir }
+    }
+
+    /// Returns the default cache directory: `turbopuffer-apigen` inside
+    /// `$XDG_CACHE_HOME`, or inside `$HOME/.cache` if that is unset.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+        };
+        Some(base.join("turbopuffer-apigen"))
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Computes the cache key for the spec at `url` with the given hash.
+    pub fn key(url: &str, hash: Option<&str>) -> String {
+        match hash {
+            Some(hash) => hash.to_ascii_lowercase(),
+            None => format!("url-{}", sha256(url)),
+        }
+    }
+
+    /// Returns the cached spec with the given key, if any. `hash` is the spec
+    /// hash the key was computed from, if any.
+    ///
+    /// Fails if the entry doesn't match the digest recorded when it was
+    /// stored, so that a corrupted entry is never used. Entries stored before
+    /// digests were recorded are checked against `hash` instead, or treated as
+    /// missing if there's none.
+    pub fn get(&self, key: &str, hash: Option<&str>) -> Result<Option<String>, Box<dyn Error>> {
+        let path = self.entry_path(key);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => Err(format!("reading spec cache {}: {e}", self.dir.display()))?,
+        };
+        let digest = match (fs::read_to_string(self.digest_path(key)), hash) {
+            (Ok(digest), _) => digest,
+            (Err(e), _) if e.kind() != io::ErrorKind::NotFound => Err(format!(
+                "reading digest of spec cache entry {}: {e}",
+                path.display()
+            ))?,
+            (Err(_), Some(hash)) => {
+                verify_hash(&content, hash)
+                    .map_err(|e| format!("spec cache entry {} is corrupt: {e}", path.display()))?;
+                return Ok(Some(content));
+            }
+            (Err(_), None) => return Ok(None),
+        };
+        if sha256(&content) != digest.trim() {
+            Err(format!(
+                "spec cache entry {} doesn't match its recorded digest",
+                path.display()
+            ))?
+        }
+        Ok(Some(content))
+    }
+
+    /// Stores a spec in the cache under the given key, along with its digest.
+    pub fn put(&self, key: &str, content: &str) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)
+            .and_then(|()| util::fs::write_atomic(&self.entry_path(key), content))
+            .and_then(|()| util::fs::write_atomic(&self.digest_path(key), &sha256(content)))
+            .map_err(|e| format!("writing spec cache {}: {e}", self.dir.display()).into())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.spec"))
+    }
+
+    fn digest_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.sha256"))
+    }
+}
+
+/// The subset of the Stainless stats file that the generator consults.
+#[derive(Debug, Deserialize)]
+pub struct StainlessStats {
+    pub openapi_spec_url: String,
+    /// The hex-encoded digest of the specification at `openapi_spec_url`.
+    /// Absent in stats files written by older versions of Stainless.
+    #[serde(default)]
+    pub openapi_spec_hash: Option<String>,
+}
+
+impl StainlessStats {
+    /// Loads the Stainless stats file from the working directory.
+    pub fn load() -> Result<StainlessStats, Box<dyn Error>> {
+        let content = fs::read_to_string(STAINLESS_STATS_PATH)
+            .map_err(|e| format!("reading {STAINLESS_STATS_PATH}: {e}"))?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+}
+
+/// Computes the hex-encoded digest of `content` using the algorithm implied
+/// by the length of `expected`.
+///
+/// Stainless records MD5 digests, but SHA-256 digests are accepted too so that
+/// hand-maintained stats files can use a stronger hash.
+pub fn hash_like(content: &str, expected: &str) -> Result<String, Box<dyn Error>> {
+    let digest = match expected.len() {
+        32 => Md5::digest(content).to_vec(),
+        64 => Sha256::digest(content).to_vec(),
+        _ => Err(format!("unrecognized OpenAPI spec hash: {expected}"))?,
+    };
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Computes the hex-encoded SHA-256 digest of `content`.
+fn sha256(content: &str) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Verifies that `content` matches the digest `expected`.
+pub fn verify_hash(content: &str, expected: &str) -> Result<(), Box<dyn Error>> {
+    let actual = hash_like(content, expected)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        Err(format!(
+            "OpenAPI spec hash mismatch: .stats.yml records {expected}, but the spec hashes to {actual}"
+        ))?
+    }
+    Ok(())
+}
+
+/// Verifies a freshly downloaded spec against the hash `expected` recorded in
+/// `.stats.yml`, if any.
+///
+/// With `allow_mismatch`, a mismatch is logged as a warning rather than
+/// returned as an error. Returns whether the spec matched, i.e., whether it
+/// may be cached.
+pub fn verify_download(
+    content: &str,
+    expected: Option<&str>,
+    allow_mismatch: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let Some(expected) = expected else {
+        return Ok(true);
+    };
+    crate::log!("verifying OpenAPI spec hash");
+    match verify_hash(content, expected) {
+        Ok(()) => Ok(true),
+        Err(e) if allow_mismatch => {
+            crate::log!("warning: {e}");
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "openapi: 3.1.0\n";
+    const SPEC_MD5: &str = "3d50c406208294075db4a64ed11309de";
+    const SPEC_SHA256: &str = "f39db8e8ede3dc2457c613e2a304e6d478f6e5ec660e4746464f41e76ac77006";
+
+    #[test]
+    fn verifies_md5_and_sha256_digests() {
+        assert_eq!(hash_like(SPEC, SPEC_MD5).unwrap(), SPEC_MD5);
+        assert_eq!(hash_like(SPEC, SPEC_SHA256).unwrap(), SPEC_SHA256);
+        verify_hash(SPEC, SPEC_MD5).unwrap();
+        verify_hash(SPEC, &SPEC_SHA256.to_ascii_uppercase()).unwrap();
+    }
+
+    #[test]
+    fn rejects_mismatched_digests() {
+        let wrong = "0".repeat(32);
+        let err = verify_hash(SPEC, &wrong).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!(
+                "OpenAPI spec hash mismatch: .stats.yml records {wrong}, but the spec hashes to {SPEC_MD5}"
+            )
+        );
+        let err = verify_hash(SPEC, "abc").unwrap_err().to_string();
+        assert_eq!(err, "unrecognized OpenAPI spec hash: abc");
+    }
+
+    #[test]
+    fn allow_hash_mismatch_downgrades_to_a_warning() {
+        let wrong = "0".repeat(64);
+        assert!(verify_download(SPEC, None, false).unwrap());
+        assert!(verify_download(SPEC, Some(SPEC_SHA256), false).unwrap());
+        assert!(verify_download(SPEC, Some(&wrong), false).is_err());
+        assert!(!verify_download(SPEC, Some(&wrong), true).unwrap());
+    }
+
+    #[test]
+    fn cache_rejects_corrupt_entries() {
+        let dir = env::temp_dir().join(format!("apigen-spec-cache-{}", std::process::id()));
+        let cache = SpecCache::new(dir.clone());
+        let key = SpecCache::key("https://example.com/openapi.yml", None);
+        assert!(cache.get(&key, None).unwrap().is_none());
+
+        cache.put(&key, SPEC).unwrap();
+        assert_eq!(cache.get(&key, None).unwrap().as_deref(), Some(SPEC));
+
+        fs::write(cache.entry_path(&key), "openapi: 3.0.0\n").unwrap();
+        let err = cache.get(&key, None).unwrap_err().to_string();
+        assert!(err.ends_with("doesn't match its recorded digest"), "{err}");
+
+        fs::remove_file(cache.digest_path(&key)).unwrap();
+        assert!(cache.get(&key, None).unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cache_checks_entries_without_digests_against_their_hash() {
+        let dir = env::temp_dir().join(format!("apigen-spec-cache-old-{}", std::process::id()));
+        let cache = SpecCache::new(dir.clone());
+        let key = SpecCache::key("https://example.com/openapi.yml", Some(SPEC_MD5));
+        // An entry stored before digests were recorded.
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(cache.entry_path(&key), SPEC).unwrap();
+        let cached = cache.get(&key, Some(SPEC_MD5)).unwrap();
+        assert_eq!(cached.as_deref(), Some(SPEC));
+
+        fs::write(cache.entry_path(&key), "openapi: 3.0.0\n").unwrap();
+        let err = cache.get(&key, Some(SPEC_MD5)).unwrap_err().to_string();
+        assert!(
+            err.contains("is corrupt: OpenAPI spec hash mismatch"),
+            "{err}"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,2 +1,4 @@
 pub mod codegen_buf;
+pub mod fs;
+pub mod glob;
 pub mod log;
@@ -95,6 +95,12 @@ impl CodegenBuf {
         self.level -= 1;
     }
 
+    /// Returns the width of the current indentation in columns, counting each
+    /// level of indentation as four columns.
+    pub fn indent_width(&self) -> usize {
+        self.level * 4
+    }
+
     /// Writes a new indented block.
     ///
     /// Specifically, if `s` is empty, the method writes the line `{` into the
@@ -124,4 +130,27 @@ impl CodegenBuf {
     pub fn unwrite_one(&mut self) {
         self.inner.pop();
     }
+
+    /// Returns a mark recording the current state of the buffer.
+    pub fn mark(&self) -> Mark {
+        Mark {
+            len: self.inner.len(),
+            level: self.level,
+        }
+    }
+
+    /// Discards everything written to the buffer since `mark` was taken.
+    pub fn rewind(&mut self, mark: Mark) {
+        self.inner.truncate(mark.len);
+        self.level = mark.level;
+    }
+}
+
+/// The state of a [`CodegenBuf`] at some point in time.
+///
+/// See [`CodegenBuf::mark`].
+#[derive(Clone, Copy, Debug)]
+pub struct Mark {
+    len: usize,
+    level: usize,
 }
@@ -0,0 +1,36 @@
+//! Filesystem utilities.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+/// Atomically replaces the file at `path` with `contents`.
+///
+/// The contents are first written to a temporary file in the same directory
+/// as `path`, which is then renamed over `path`. Readers therefore observe
+/// either the old contents or the new contents, never a partial write.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a file path: {}", path.display()),
+        )
+    })?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(format!(".tmp{}", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let res = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
@@ -0,0 +1,72 @@
+//! Glob matching.
+
+/// Reports whether `s` matches the glob `pattern`.
+///
+/// The pattern syntax is deliberately minimal: `*` matches any sequence of
+/// characters (including the empty sequence) and `?` matches any single
+/// character. All other characters match themselves.
+pub fn matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    // Classic backtracking matcher: remember the position of the most recent
+    // `*` and retry from there, consuming one more character, on mismatch.
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_i)) => {
+                    p = star_p + 1;
+                    i = star_i + 1;
+                    backtrack = Some((star_p, star_i + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn literals() {
+        assert!(matches("Filter", "Filter"));
+        assert!(!matches("Filter", "Filters"));
+        assert!(!matches("Filters", "Filter"));
+        assert!(matches("", ""));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("Filter*", "Filter"));
+        assert!(matches("Filter*", "FilterAnd"));
+        assert!(matches("*Internal", "QueryInternal"));
+        assert!(!matches("*Internal", "InternalQuery"));
+        assert!(matches("A*B*C", "AxxBxxBxxC"));
+        assert!(!matches("A*B*C", "AxxBxx"));
+        assert!(matches("**a", "a"));
+    }
+
+    #[test]
+    fn single_characters() {
+        assert!(matches("V?", "V1"));
+        assert!(!matches("V?", "V"));
+        assert!(!matches("V?", "V12"));
+        assert!(matches("?*?", "ab"));
+        assert!(matches("é?", "éü"));
+    }
+}